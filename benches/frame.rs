@@ -0,0 +1,41 @@
+//! Per-frame cost of drawing the pentagon offscreen on a software adapter.
+//!
+//! `static mesh` draws the buffers uploaded at creation.
+//! `dirty mesh` marks the mesh dirty before every frame, so that its vertices and indices are written again.
+//! The device is waited for after each frame, so that the time of the GPU work is included.
+//! Run with `cargo bench --bench frame`.
+
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use wgpu_core_demo::{render::WgpuRenderer, runtime, texture::{self, Texture}};
+
+const WARMUP_FRAMES: u32 = 20;
+const FRAMES: u32 = 500;
+
+fn measure(renderer: &mut WgpuRenderer, mut before_frame: impl FnMut(&mut WgpuRenderer)) -> Duration {
+    for _ in 0..WARMUP_FRAMES {
+        before_frame(renderer);
+        renderer.render().unwrap();
+        renderer.context().wait_idle().unwrap();
+    }
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        before_frame(renderer);
+        renderer.render().unwrap();
+        renderer.context().wait_idle().unwrap();
+    }
+    start.elapsed() / FRAMES
+}
+
+fn main() {
+    let context = Arc::new(runtime::init_headless_context(true, runtime::RenderOptions::default()).unwrap());
+    let texture = Texture::from_bytes(&context, texture::HAPPY_TREE).unwrap();
+    let mut renderer = WgpuRenderer::new_offscreen(context, (256, 256), &texture).unwrap();
+
+    let dirty = measure(&mut renderer, |renderer| renderer.meshes_mut()[0].mark_dirty());
+    let resident = measure(&mut renderer, |_| {});
+
+    println!("dirty mesh:  {dirty:>10.2?} / frame");
+    println!("static mesh: {resident:>10.2?} / frame");
+}
@@ -1,11 +1,13 @@
-use std::{collections::HashMap, sync::Arc};
-use winit::{application::ApplicationHandler, dpi::PhysicalSize, event::{KeyEvent, StartCause, WindowEvent}, event_loop::{ActiveEventLoop, EventLoop, EventLoopProxy}, keyboard::{KeyCode, PhysicalKey}, platform::macos::WindowAttributesExtMacOS, window::{Window, WindowAttributes, WindowId}};
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::Arc, time::{Duration, Instant, SystemTime}};
+use winit::{application::ApplicationHandler, dpi::{PhysicalPosition, PhysicalSize}, event::{ElementState, KeyEvent, Modifiers, MouseButton, MouseScrollDelta, StartCause, WindowEvent}, event_loop::{ActiveEventLoop, EventLoop, EventLoopProxy}, keyboard::{KeyCode, PhysicalKey}, window::{Window, WindowId}};
 
-use crate::{render, runtime};
+use crate::{camera::CameraControl, compute, error::RenderError, mesh, obj, render, runtime, scene, shader_watch, texture, window::{AppHandle, WindowKey, WindowSpec}};
 
 pub struct App {
     proxy_loop: EventLoopProxy<runtime::UserEvent>,
     suspended: bool,
+    shader_path: Option<PathBuf>,
+    initial_window: WindowSpec,
     state: AppState
 }
 impl App {
@@ -13,23 +15,51 @@ impl App {
         Self {
             proxy_loop: event_loop.create_proxy(),
             suspended: true,
+            shader_path: None,
+            initial_window: WindowSpec::default(),
             state: AppState::new(terminate_on_empty),
         }
     }
+
+    /// Enables the development mode that reloads the shader whenever the WGSL file at `path` changes.
+    pub fn watch_shader(&mut self, path: impl Into<PathBuf>) {
+        self.shader_path = Some(path.into());
+    }
+
+    pub fn render_options(&self) -> &runtime::RenderOptions {
+        &self.state.options
+    }
+
+    /// Overrides the settings of the rendering context. Must be called before the event loop starts.
+    pub fn set_render_options(&mut self, options: runtime::RenderOptions) {
+        self.state.options = options;
+    }
+
+    /// A handle to open and control windows from other threads, valid while the event loop runs.
+    pub fn handle(&self) -> AppHandle {
+        AppHandle::new(self.proxy_loop.clone())
+    }
+
+    /// Overrides the window opened at startup. Must be called before the event loop starts.
+    pub fn set_initial_window(&mut self, spec: WindowSpec) {
+        self.initial_window = spec;
+    }
 }
 
 impl ApplicationHandler<runtime::UserEvent> for App {
     fn resumed(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
-        if self.state.render_context.is_some() {
-            self.state.resume_app();
-            self.suspended = false;
-        }
+        self.state.resume_app();
+        self.suspended = false;
     }
 
     fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
        self.suspended = true;
     }
 
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        self.state.save_pipeline_cache();
+    }
+
     fn window_event(
         &mut self,
         event_loop: &winit::event_loop::ActiveEventLoop,
@@ -44,11 +74,56 @@ impl ApplicationHandler<runtime::UserEvent> for App {
                 WindowEvent::KeyboardInput { event: KeyEvent{ physical_key: PhysicalKey::Code(KeyCode::Escape), .. }, .. } => {
                     *status = HandleStatus::Closed;
                 }
+                WindowEvent::ModifiersChanged(modifiers) => {
+                    entry.modifiers = modifiers;
+                }
+                WindowEvent::KeyboardInput { event: KeyEvent{ physical_key: PhysicalKey::Code(KeyCode::KeyN), state: ElementState::Pressed, repeat: false, .. }, .. } if entry.modifiers.state().control_key() => {
+                    let event = runtime::UserEvent::RequestNew { key: WindowKey::next(), spec: WindowSpec::default() };
+                    if self.proxy_loop.send_event(event).is_err() {
+                        log::error!("Failed to request a new window, the event loop has exited");
+                    }
+                }
+                WindowEvent::KeyboardInput { event: KeyEvent{ physical_key: PhysicalKey::Code(KeyCode::KeyW), state: ElementState::Pressed, repeat: false, .. }, .. } if entry.modifiers.state().control_key() => {
+                    *status = HandleStatus::Closed;
+                }
+                WindowEvent::KeyboardInput { event: KeyEvent{ physical_key: PhysicalKey::Code(KeyCode::F12), state: ElementState::Pressed, repeat: false, .. }, .. } => {
+                    entry.handle_capture();
+                }
+                WindowEvent::KeyboardInput { event: KeyEvent{ physical_key: PhysicalKey::Code(KeyCode::KeyF), state: ElementState::Pressed, repeat: false, .. }, .. } => {
+                    entry.renderer.set_wireframe(! entry.renderer.wireframe());
+                }
+                WindowEvent::KeyboardInput { event: KeyEvent{ physical_key: PhysicalKey::Code(KeyCode::KeyG), state: ElementState::Pressed, repeat: false, .. }, .. } => {
+                    entry.handle_grayscale();
+                }
+                WindowEvent::KeyboardInput { event: KeyEvent{ physical_key: PhysicalKey::Code(KeyCode::KeyN), state: ElementState::Pressed, repeat: false, .. }, .. } => {
+                    entry.handle_nearest_filter();
+                }
+                WindowEvent::KeyboardInput { event: KeyEvent{ physical_key: PhysicalKey::Code(KeyCode::KeyV), state: ElementState::Pressed, repeat: false, .. }, .. } => {
+                    entry.handle_vsync();
+                }
+                WindowEvent::KeyboardInput { event: KeyEvent{ physical_key: PhysicalKey::Code(code), state: ElementState::Pressed, repeat, .. }, .. } => {
+                    entry.handle_camera_key(code, repeat);
+                }
+                WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                    entry.dragging = state == ElementState::Pressed;
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    entry.handle_cursor_moved(position);
+                }
+                WindowEvent::MouseWheel { delta, .. } => {
+                    entry.handle_scroll(delta);
+                }
                 WindowEvent::Resized(size) => {
                     entry.handle_resize(size);
                 }
+                WindowEvent::DroppedFile(ref path) => {
+                    entry.handle_dropped_file(path);
+                }
                 WindowEvent::RedrawRequested if ! self.suspended => {
-                    entry.handle_draw();
+                    if let Err(err) = entry.handle_draw() {
+                        log::error!("Failed to render, closing the window (cause: {err})");
+                        *status = HandleStatus::Closed;
+                    }
                 }
                 _ => {
                     log::warn!("Event handler is not implemented: (id: {window_id:?}, event: {event:?}");
@@ -59,17 +134,55 @@ impl ApplicationHandler<runtime::UserEvent> for App {
 
     fn new_events(&mut self, event_loop: &ActiveEventLoop, cause: StartCause) {
         if cause == StartCause::Init {
-            if let Err(err) = self.state.init_render_context(event_loop, self.proxy_loop.clone()) {
-                log::error!("Failed to create gpu rendering context (cause: {err})");
+            // The rendering context is created along with this window
+            let event = runtime::UserEvent::RequestNew { key: WindowKey::next(), spec: self.initial_window.clone() };
+            if self.proxy_loop.send_event(event).is_err() {
+                log::error!("Failed to request the initial window, the event loop has exited");
                 event_loop.exit();
+                return;
+            }
+            if let Some(path) = self.shader_path.clone() && let Err(err) = shader_watch::spawn(path, self.proxy_loop.clone()) {
+                log::error!("Failed to watch the shader (cause: {err})");
             }
         }
     }
 
     fn user_event(&mut self, event_loop: &ActiveEventLoop, event: runtime::UserEvent) {
         match event {
-            runtime::UserEvent::RequestNew => {
-                self.state.add_new_window(event_loop).unwrap();
+            runtime::UserEvent::RequestNew { key, spec } => {
+                if let Err(err) = self.state.add_new_window(event_loop, self.proxy_loop.clone(), key, &spec) {
+                    log::error!("Failed to open the window `{}` (cause: {err})", spec.title);
+                    if self.state.render_context.is_none() {
+                        log::error!("No gpu rendering context could be created, exiting");
+                        event_loop.exit();
+                    }
+                }
+            }
+            runtime::UserEvent::Close(key) => {
+                if let Some(id) = self.state.window_keys.get(&key).copied() {
+                    self.state.remove_window(id, event_loop);
+                }
+            }
+            runtime::UserEvent::SetTitle(key, title) => {
+                if let Some(entry) = self.state.entry_mut(key) {
+                    entry.window.set_title(&title);
+                }
+            }
+            runtime::UserEvent::Resize(key, (width, height)) => {
+                if let Some(entry) = self.state.entry_mut(key) && let Some(size) = entry.window.request_inner_size(PhysicalSize::new(width, height)) {
+                    // Applied at once, in which case no `Resized` event may follow
+                    entry.handle_resize(size);
+                }
+            }
+            runtime::UserEvent::ReloadShader { path, source } => {
+                self.state.reload_shader(&path, &source);
+            }
+            runtime::UserEvent::DeviceLost(message) => {
+                log::warn!("Device is lost, recreating the rendering context (reason: {message})");
+                if let Err(err) = self.state.recover_device(self.proxy_loop.clone()) {
+                    log::error!("Failed to recover from the device lost (cause: {err})");
+                    event_loop.exit();
+                }
             }
         }
     }
@@ -77,49 +190,121 @@ impl ApplicationHandler<runtime::UserEvent> for App {
 
 struct AppState {
     app_entries: HashMap<WindowId, Entry>,
+    window_keys: HashMap<WindowKey, WindowId>,
     render_context: Option<Arc<runtime::RenderContext>>,
+    default_texture: Option<texture::Texture>,
+    options: runtime::RenderOptions,
     terminate_on_empty: bool,
 }
 impl AppState {
-    const DEFAULT_SIZE: PhysicalSize<u32> = PhysicalSize::new(1024, 768);
-
     fn new(terminate_on_empty: bool) -> Self {
         Self {
             app_entries: HashMap::new(),
+            window_keys: HashMap::new(),
             render_context: None,
+            default_texture: None,
+            options: runtime::RenderOptions {
+                sample_count: 4,
+                pipeline_cache_dir: Some(std::env::temp_dir().join("wgpu-core-demo")),
+                ..Default::default()
+            },
             terminate_on_empty,
         }
     }
 
-    fn init_render_context(&mut self, event_loop: &ActiveEventLoop, event_loop_proxy: EventLoopProxy<runtime::UserEvent>) -> Result<(), anyhow::Error> {
-        let attr = WindowAttributes::default()
-            .with_inner_size(PhysicalSize::new(1, 1))
-            .with_transparent(true)
-            .with_has_shadow(false)
-        ;
-        let window = Arc::new(event_loop.create_window(attr).unwrap());
+    /// Creates the rendering context on an adapter able to present to `window`, the first one opened.
+    fn init_render_context(&mut self, window: Arc<Window>, event_loop_proxy: EventLoopProxy<runtime::UserEvent>) -> Result<Arc<runtime::RenderContext>, anyhow::Error> {
+        let context = runtime::init_render_context(Box::new(WindowWrapper(window)), self.options.clone())?;
+        notify_device_lost(&context, event_loop_proxy);
+        self.default_texture = Some(texture::Texture::from_bytes(&context, texture::HAPPY_TREE)?);
+
+        let context = Arc::new(context);
+        self.render_context = Some(context.clone());
+        Ok(context)
+    }
 
-        let context = runtime::init_render_context(Box::new(WindowWrapper(window)))?;
-        self.render_context = Some(Arc::new(context));
+    fn add_new_window(&mut self, event_loop: &ActiveEventLoop, event_loop_proxy: EventLoopProxy<runtime::UserEvent>, key: WindowKey, spec: &WindowSpec) -> Result<(), anyhow::Error> {
+        let window = Arc::new(event_loop.create_window(spec.attributes())?);
+        let context = match self.render_context.as_ref() {
+            Some(context) => context.clone(),
+            None => self.init_render_context(window.clone(), event_loop_proxy)?,
+        };
+        let Some(texture) = self.default_texture.as_ref() else { anyhow::bail!("Default texture is not loaded") };
 
-        event_loop_proxy.send_event(runtime::UserEvent::RequestNew).map_err(|err| anyhow::anyhow!("Failed to create new window (reson: {err}"))?;
+        // The platform may not honor the requested size, e.g. in fullscreen
+        let size = window.inner_size();
+        let surface_id = runtime::create_surface(&context, WindowWrapper(window.clone()))?;
+        let renderer = render::WgpuRenderer::new(context, surface_id, (size.width, size.height), texture)?;
+        log::info!("Surface format of `{}`: {:?}", window.title(), renderer.color_format());
+
+        self.window_keys.insert(key, window.id());
+        self.app_entries.insert(window.id(), Entry::new(key, window, renderer));
         Ok(())
     }
 
-    fn add_new_window(&mut self, event_loop: &ActiveEventLoop) -> Result<(), anyhow::Error> {
-        let Some(context) = self.render_context.as_ref() else { anyhow::bail!("GPU rendering context is not initialized") };
+    fn entry_mut(&mut self, key: WindowKey) -> Option<&mut Entry> {
+        self.window_keys.get(&key).and_then(|id| self.app_entries.get_mut(id))
+    }
 
-        let attr = WindowAttributes::default()
-            .with_inner_size(Self::DEFAULT_SIZE)
-        ;
-        let window = Arc::new(event_loop.create_window(attr)?);
-        let surface_id = runtime::create_surface(context, WindowWrapper(window.clone()))?;
-        let renderer = render::WgpuRenderer::new(context.clone(), surface_id, (Self::DEFAULT_SIZE.width, Self::DEFAULT_SIZE.height))?;
+    fn remove_window(&mut self, id: WindowId, event_loop: &ActiveEventLoop) {
+        if let Some(entry) = self.app_entries.remove(&id) {
+            self.window_keys.remove(&entry.key);
+        }
+        if self.terminate_on_empty && self.app_entries.is_empty() {
+            event_loop.exit();
+        }
+    }
+
+    /// Replaces the lost rendering context and moves every window onto the new one.
+    fn recover_device(&mut self, event_loop_proxy: EventLoopProxy<runtime::UserEvent>) -> Result<(), anyhow::Error> {
+        let Some(lost) = self.render_context.as_ref() else { anyhow::bail!("GPU rendering context is not initialized") };
 
-        self.app_entries.insert(window.id(), Entry::new(window, renderer));
+        let surface_id = self.app_entries.values().find_map(|entry| entry.renderer.surface_id());
+        let context = Arc::new(runtime::recreate_render_context(lost, surface_id)?);
+        notify_device_lost(&context, event_loop_proxy);
+        let default_texture = texture::Texture::from_bytes(&context, texture::HAPPY_TREE)?;
+
+        for entry in self.app_entries.values_mut() {
+            entry.source = match entry.texture_path.as_ref().map(|path| texture::Texture::from_path(&context, path)) {
+                Some(Ok(texture)) => Some(texture),
+                Some(Err(err)) => {
+                    log::warn!("Failed to reload the texture, fall back to the default (cause: {err})");
+                    None
+                }
+                None => None,
+            };
+            entry.filtered = None;
+            entry.grayscale_filter = None;
+            entry.renderer.recreate(context.clone(), entry.source.as_ref().unwrap_or(&default_texture))?;
+            if (entry.grayscale || entry.sampler != texture::SamplerOptions::default()) && let Err(err) = entry.show_texture() {
+                log::warn!("Failed to prepare the texture (cause: {err})");
+            }
+        }
+
+        self.default_texture = Some(default_texture);
+        self.render_context = Some(context);
         Ok(())
     }
 
+    fn reload_shader(&mut self, path: &Path, source: &str) {
+        let Some(context) = self.render_context.as_ref() else { return };
+
+        match context.reload_shader(source) {
+            Ok(_) => log::info!("Reloaded the shader from {}", path.display()),
+            Err(err) => log::error!("Failed to reload {}, keep using the last good pipeline (cause: {err})", path.display()),
+        }
+    }
+
+    fn save_pipeline_cache(&self) {
+        let Some(context) = self.render_context.as_ref() else { return };
+
+        match (context.save_pipeline_cache(), context.pipeline_cache_path()) {
+            (Ok(_), Some(path)) => log::info!("Saved the pipeline cache to {}", path.display()),
+            (Ok(_), None) => {}
+            (Err(err), _) => log::warn!("Failed to save the pipeline cache (cause: {err})"),
+        }
+    }
+
     fn handle(&mut self, id: WindowId, event_loop: &ActiveEventLoop, mut callback: impl FnMut(&mut Entry, &mut HandleStatus)) {
         let mut status = HandleStatus::None;
 
@@ -127,10 +312,7 @@ impl AppState {
             callback(entry, &mut status);
         }
         if status == HandleStatus::Closed {
-            self.app_entries.remove(&id);
-            if self.terminate_on_empty && self.app_entries.is_empty() {
-                event_loop.exit();
-            }
+            self.remove_window(id, event_loop);
         }
     }
 
@@ -148,18 +330,44 @@ enum HandleStatus {
 }
 
 struct Entry {
+    key: WindowKey,
     dirty_resized: Option<(u32, u32)>,
+    cursor: Option<PhysicalPosition<f64>>,
+    dragging: bool,
+    /// Reported to each window on its own, so that a key released elsewhere does not stick.
+    modifiers: Modifiers,
     window: Arc<Window>,
     renderer: render::WgpuRenderer,
+    texture_path: Option<PathBuf>,
+    /// The dropped texture before the filter, `None` for the default one until it is filtered or sampled otherwise.
+    source: Option<texture::Texture>,
+    /// The source through the grayscale filter, while it is on.
+    filtered: Option<texture::Texture>,
+    /// Built on the first use of the filter, and again after the device is lost.
+    grayscale_filter: Option<compute::ImageFilter>,
+    grayscale: bool,
+    sampler: texture::SamplerOptions,
+    frame_rate: FrameRate,
 }
 impl Entry {
-    fn new(window: Arc<Window>, renderer: render::WgpuRenderer) -> Self {
+    fn new(key: WindowKey, window: Arc<Window>, renderer: render::WgpuRenderer) -> Self {
         let size = window.inner_size();
 
         Self {
+            key,
             dirty_resized: Some((size.width, size.height)),
+            cursor: None,
+            dragging: false,
+            modifiers: Modifiers::default(),
             window,
             renderer,
+            texture_path: None,
+            source: None,
+            filtered: None,
+            grayscale_filter: None,
+            grayscale: false,
+            sampler: texture::SamplerOptions::default(),
+            frame_rate: FrameRate::new(),
         }
     }
 
@@ -167,16 +375,259 @@ impl Entry {
         self.dirty_resized = Some((size.width, size.height));
     }
 
-    fn handle_draw(&mut self) {
+    /// Tab switches between orbit and fly controls, P between perspective and orthographic projections.
+    /// WASD, Q and E move the camera in fly mode.
+    fn handle_camera_key(&mut self, code: KeyCode, repeat: bool) {
+        // The camera is only borrowed mutably by the keys changing it, which marks it dirty
+        match code {
+            KeyCode::Tab if ! repeat => {
+                let camera = self.renderer.camera_mut();
+                camera.control = match camera.control {
+                    CameraControl::Orbit => CameraControl::Fly,
+                    CameraControl::Fly => CameraControl::Orbit,
+                };
+            }
+            KeyCode::KeyP if ! repeat => {
+                let camera = self.renderer.camera_mut();
+                camera.projection = camera.projection.toggled(camera.distance());
+            }
+            _ if self.renderer.camera().control == CameraControl::Fly => {
+                let (forward, right, up) = match code {
+                    KeyCode::KeyW => (FLY_STEP, 0.0, 0.0),
+                    KeyCode::KeyS => (-FLY_STEP, 0.0, 0.0),
+                    KeyCode::KeyD => (0.0, FLY_STEP, 0.0),
+                    KeyCode::KeyA => (0.0, -FLY_STEP, 0.0),
+                    KeyCode::KeyE => (0.0, 0.0, FLY_STEP),
+                    KeyCode::KeyQ => (0.0, 0.0, -FLY_STEP),
+                    _ => return,
+                };
+                self.renderer.camera_mut().fly(forward, right, up);
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_cursor_moved(&mut self, position: PhysicalPosition<f64>) {
+        let last = self.cursor.replace(position);
+        let Some(last) = last.filter(|_| self.dragging) else { return };
+
+        let (dx, dy) = ((position.x - last.x) as f32 * ROTATE_SPEED, (position.y - last.y) as f32 * ROTATE_SPEED);
+        let camera = self.renderer.camera_mut();
+        match camera.control {
+            // Dragging moves the scene along with the cursor
+            CameraControl::Orbit => camera.orbit(-dx, dy),
+            CameraControl::Fly => camera.look(dx, -dy),
+        }
+    }
+
+    fn handle_scroll(&mut self, delta: MouseScrollDelta) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
+        };
+
+        let camera = self.renderer.camera_mut();
+        match camera.control {
+            CameraControl::Orbit => camera.zoom(ZOOM_PER_LINE.powf(lines)),
+            CameraControl::Fly => camera.fly(lines * FLY_STEP, 0.0, 0.0),
+        }
+    }
+
+    fn handle_dropped_file(&mut self, path: &Path) {
+        let extension = path.extension().map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("obj") => return self.handle_dropped_model(path),
+            Some("gltf" | "glb") => return self.handle_dropped_scene(path),
+            _ => {}
+        }
+
+        let texture = match texture::Texture::from_path(self.renderer.context(), path) {
+            Ok(texture) => texture,
+            Err(err) => {
+                log::error!("Failed to load the texture (cause: {err})");
+                return;
+            }
+        };
+        let previous = self.source.replace(texture);
+        match self.show_texture() {
+            Ok(_) => self.texture_path = Some(path.to_path_buf()),
+            Err(err) => {
+                log::error!("Failed to prepare the texture (cause: {err})");
+                self.source = previous;
+            }
+        }
+    }
+
+    /// G toggles the grayscale filter over the texture.
+    fn handle_grayscale(&mut self) {
+        self.grayscale = ! self.grayscale;
+        if let Err(err) = self.show_texture() {
+            log::error!("Failed to filter the texture (cause: {err})");
+        }
+    }
+
+    /// N toggles between nearest and linear filtering, to inspect the texels of pixel art.
+    fn handle_nearest_filter(&mut self) {
+        self.sampler = match self.sampler.is_nearest() {
+            true => texture::SamplerOptions::default(),
+            false => texture::SamplerOptions::default().nearest(),
+        };
+        if let Err(err) = self.apply_sampler() {
+            log::error!("Failed to change the sampler (cause: {err})");
+        }
+    }
+
+    /// V toggles vertical sync, to measure the frame rate without it.
+    fn handle_vsync(&mut self) {
+        let preference = match self.renderer.present_mode() {
+            wgpu::wgt::PresentMode::Fifo | wgpu::wgt::PresentMode::FifoRelaxed => wgpu::wgt::PresentMode::AutoNoVsync,
+            _ => wgpu::wgt::PresentMode::AutoVsync,
+        };
+        match self.renderer.set_present_modes(&[preference]) {
+            Ok(mode) => log::info!("Present mode: {mode:?}"),
+            Err(err) => log::error!("Failed to change the present mode (cause: {err})"),
+        }
+    }
+
+    /// Filters the source texture as chosen in this window, and shows it with the chosen sampler.
+    fn show_texture(&mut self) -> Result<(), anyhow::Error> {
+        let context = self.renderer.context();
+        let source = match self.source.take() {
+            Some(source) => source,
+            None => texture::Texture::from_bytes(context, texture::HAPPY_TREE)?,
+        };
+        let source = self.source.insert(source);
+        if *source.sampler_options() != self.sampler {
+            source.set_sampler(context, self.sampler.clone())?;
+        }
+
+        self.filtered = match self.grayscale {
+            true => {
+                let filter = match self.grayscale_filter.take() {
+                    Some(filter) => filter,
+                    None => compute::ImageFilter::new(context, "Grayscale", compute::GRAYSCALE)?,
+                };
+                let mut filtered = self.grayscale_filter.insert(filter).apply(context, source)?;
+                filtered.set_sampler(context, self.sampler.clone())?;
+                Some(filtered)
+            }
+            false => None,
+        };
+        self.renderer.set_texture(self.filtered.as_ref().unwrap_or(source))?;
+        Ok(())
+    }
+
+    /// Switches the texture and the materials to the sampler chosen in this window, without filtering again.
+    fn apply_sampler(&mut self) -> Result<(), anyhow::Error> {
+        self.renderer.set_material_sampler(self.sampler.clone())?;
+        if self.source.is_none() { return self.show_texture() }
+
+        let context = self.renderer.context();
+        for texture in self.source.iter_mut().chain(self.filtered.iter_mut()) {
+            texture.set_sampler(context, self.sampler.clone())?;
+        }
+        if let Some(texture) = self.filtered.as_ref().or(self.source.as_ref()) {
+            self.renderer.set_texture(texture)?;
+        }
+        Ok(())
+    }
+
+    fn handle_dropped_model(&mut self, path: &Path) {
+        let groups = match obj::load(path) {
+            Ok(groups) => groups,
+            Err(err) => {
+                log::error!("Failed to load the model (cause: {err})");
+                return;
+            }
+        };
+        let meshes = groups.into_iter()
+            .map(|group| mesh::Mesh::new(self.renderer.context(), group.data))
+            .collect::<Result<Vec<_>, _>>()
+        ;
+        match meshes {
+            Ok(meshes) => self.renderer.set_meshes(meshes),
+            Err(err) => log::error!("Failed to upload the model (cause: {err})"),
+        }
+    }
+
+    fn handle_dropped_scene(&mut self, path: &Path) {
+        let scene = match scene::load_gltf(path) {
+            Ok(scene) => scene,
+            Err(err) => {
+                log::error!("Failed to load the scene (cause: {err})");
+                return;
+            }
+        };
+        if let Err(err) = self.renderer.set_scene(&scene) {
+            log::error!("Failed to upload the scene (cause: {err})");
+        }
+    }
+
+    fn handle_capture(&mut self) {
+        let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis();
+        let path = PathBuf::from(format!("screenshot-{timestamp}.png"));
+
+        if let Err(err) = self.renderer.request_capture(path) {
+            log::error!("Failed to request frame capture (cause: {err})");
+        }
+    }
+
+    fn handle_draw(&mut self) -> Result<(), RenderError> {
         if let Some(size) = self.dirty_resized.take() {
             self.renderer.request_resize(size);
         }
 
-        self.renderer.render().unwrap();
+        match self.renderer.render() {
+            Ok(_) => {
+                if let Some(fps) = self.frame_rate.tick() {
+                    log::info!("{fps:.1} fps ({:?})", self.renderer.present_mode());
+                }
+            }
+            Err(err) if err.is_recoverable() => log::warn!("Skipped the frame (cause: {err})"),
+            Err(err) => return Err(err),
+        }
         self.window.request_redraw();
+        Ok(())
+    }
+}
+
+/// Counts the frames drawn by a window over periods of a second.
+struct FrameRate {
+    frames: u32,
+    since: Instant,
+}
+impl FrameRate {
+    fn new() -> Self {
+        Self { frames: 0, since: Instant::now() }
+    }
+
+    /// Returns the frames per second once a period is over.
+    fn tick(&mut self) -> Option<f64> {
+        self.frames += 1;
+        let elapsed = self.since.elapsed();
+        if elapsed < Duration::from_secs(1) { return None }
+
+        let fps = self.frames as f64 / elapsed.as_secs_f64();
+        *self = Self::new();
+        Some(fps)
     }
 }
 
+/// Radians turned by a pixel of cursor movement.
+const ROTATE_SPEED: f32 = 0.005;
+/// Zoom factor applied by a line of scrolling.
+const ZOOM_PER_LINE: f32 = 0.9;
+const PIXELS_PER_LINE: f32 = 20.0;
+/// World units moved by a key press in fly mode.
+const FLY_STEP: f32 = 0.1;
+
+fn notify_device_lost(context: &runtime::RenderContext, event_loop_proxy: EventLoopProxy<runtime::UserEvent>) {
+    context.on_device_lost(move |message| {
+        // The event loop may already be gone while shutting down
+        let _ = event_loop_proxy.send_event(runtime::UserEvent::DeviceLost(message));
+    });
+}
+
 struct WindowWrapper(Arc<Window>);
 
 impl runtime::AsRawWindow for WindowWrapper {
@@ -0,0 +1,194 @@
+use std::borrow::Cow;
+
+use glam::{Mat4, Vec3};
+use wgpu::wgc::id::{BindGroupId, BufferId};
+
+use crate::{error::RenderError, runtime, wgpu_resource::AutoDropId};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// `fov_y` is the vertical field of view in radians.
+    Perspective { fov_y: f32, near: f32, far: f32 },
+    /// `height` is the extent of the view volume in world units, the width follows the aspect ratio.
+    Orthographic { height: f32, near: f32, far: f32 },
+}
+impl Projection {
+    fn matrix(&self, aspect: f32) -> Mat4 {
+        match *self {
+            Projection::Perspective { fov_y, near, far } => Mat4::perspective_rh(fov_y, aspect, near, far),
+            Projection::Orthographic { height, near, far } => {
+                let (half_width, half_height) = (0.5 * height * aspect, 0.5 * height);
+                Mat4::orthographic_rh(-half_width, half_width, -half_height, half_height, near, far)
+            }
+        }
+    }
+
+    /// The other kind of projection, keeping the clip planes.
+    /// The extent of the orthographic view matches what the perspective one shows at `distance`.
+    pub fn toggled(&self, distance: f32) -> Self {
+        match *self {
+            Projection::Perspective { fov_y, near, far } => Projection::Orthographic { height: 2.0 * distance * (0.5 * fov_y).tan(), near, far },
+            Projection::Orthographic { height, near, far } => Projection::Perspective { fov_y: 2.0 * (0.5 * height / distance).atan(), near: near.max(0.01), far },
+        }
+    }
+}
+
+/// How mouse and keyboard input moves the camera.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraControl {
+    /// Rotates and zooms around the target.
+    Orbit,
+    /// Moves the eye freely, the target follows.
+    Fly,
+}
+
+/// A camera looking from `eye` to `target`, right-handed with `up` as the vertical axis.
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub eye: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    pub projection: Projection,
+    pub control: CameraControl,
+    aspect: f32,
+}
+impl Camera {
+    /// The default camera maps the square between -1 and 1 on the XY plane to the whole viewport when it is square,
+    /// so that geometry written in clip space is shown as it is.
+    pub fn new() -> Self {
+        Self {
+            eye: Vec3::new(0.0, 0.0, 1.0),
+            target: Vec3::ZERO,
+            up: Vec3::Y,
+            projection: Projection::Orthographic { height: 2.0, near: 0.0, far: 2.0 },
+            control: CameraControl::Orbit,
+            aspect: 1.0,
+        }
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    pub fn set_aspect(&mut self, (width, height): (u32, u32)) {
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
+    }
+
+    pub fn view_projection(&self) -> Mat4 {
+        self.projection.matrix(self.aspect) * Mat4::look_at_rh(self.eye, self.target, self.up)
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.eye.distance(self.target)
+    }
+
+    /// Rotates the eye around the target by `yaw` and `pitch` in radians, a positive pitch raises the eye.
+    /// The pitch stops short of the poles so that the view never flips.
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        let offset = self.eye - self.target;
+        let radius = offset.length();
+        let current_pitch = (offset.dot(self.up) / radius).clamp(-1.0, 1.0).asin();
+        let pitch = (current_pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH) - current_pitch;
+
+        let right = self.up.cross(offset).normalize_or_zero();
+        let rotation = glam::Quat::from_axis_angle(self.up, yaw) * glam::Quat::from_axis_angle(right, -pitch);
+        self.eye = self.target + rotation * offset;
+    }
+
+    /// Moves the eye toward the target, `factor` below 1 zooms in.
+    pub fn zoom(&mut self, factor: f32) {
+        let offset = (self.eye - self.target) * factor;
+        self.eye = self.target + offset.clamp_length_min(MIN_DISTANCE);
+
+        if let Projection::Orthographic { height, near, far } = self.projection {
+            self.projection = Projection::Orthographic { height: height * factor, near, far };
+        }
+    }
+
+    /// Moves both the eye and the target, relative to the view direction.
+    pub fn fly(&mut self, forward: f32, right: f32, up: f32) {
+        let direction = (self.target - self.eye).normalize_or_zero();
+        let side = direction.cross(self.up).normalize_or_zero();
+        let delta = direction * forward + side * right + self.up * up;
+
+        self.eye += delta;
+        self.target += delta;
+    }
+
+    /// Turns the view direction by `yaw` and `pitch` in radians, keeping the eye in place.
+    /// A positive yaw turns to the right, a positive pitch looks up.
+    pub fn look(&mut self, yaw: f32, pitch: f32) {
+        let offset = self.target - self.eye;
+        let radius = offset.length();
+        let current_pitch = (offset.dot(self.up) / radius).clamp(-1.0, 1.0).asin();
+        let pitch = (current_pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH) - current_pitch;
+
+        let side = offset.cross(self.up).normalize_or_zero();
+        let rotation = glam::Quat::from_axis_angle(self.up, -yaw) * glam::Quat::from_axis_angle(side, pitch);
+        self.target = self.eye + rotation * offset;
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const MAX_PITCH: f32 = 89.0 * std::f32::consts::PI / 180.0;
+const MIN_DISTANCE: f32 = 0.01;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::NoUninit)]
+struct CameraUniform {
+    view_projection: Mat4,
+}
+
+/// The uniform buffer holding the camera matrix, bound at group 1.
+pub(crate) struct CameraBinding {
+    buffer: AutoDropId<BufferId>,
+    pub(crate) bind_group: AutoDropId<BindGroupId>,
+}
+impl CameraBinding {
+    pub(crate) fn new(context: &runtime::RenderContext, camera: &Camera) -> Result<Self, RenderError> {
+        let desc = wgpu::wgt::BufferDescriptor {
+            label: Some(Cow::Borrowed("Camera buffer")),
+            size: size_of::<CameraUniform>() as u64,
+            mapped_at_creation: false,
+            usage: wgpu::wgt::BufferUsages::UNIFORM | wgpu::wgt::BufferUsages::COPY_DST,
+        };
+        let (buffer_id, err) = context.instance.0.device_create_buffer(context.device.id, &desc, None);
+        let buffer = context.instance.as_auto_drop(buffer_id);
+        if let Some(err) = err { return Err(RenderError::resource("Camera buffer", err)) }
+
+        let desc = wgpu::wgc::binding_model::BindGroupDescriptor {
+            label: Some(Cow::Borrowed("Camera bind group")),
+            layout: context.camera_bind_group_layout.id,
+            entries: Cow::Borrowed(&[
+                wgpu::wgc::binding_model::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::wgc::binding_model::BindingResource::Buffer(wgpu::wgc::binding_model::BufferBinding {
+                        buffer: buffer.id,
+                        offset: 0,
+                        size: None,
+                    }),
+                },
+            ]),
+        };
+        let (group_id, err) = context.instance.0.device_create_bind_group(context.device.id, &desc, None);
+        let bind_group = context.instance.as_auto_drop(group_id);
+        if let Some(err) = err { return Err(RenderError::resource("Camera bind group", err)) }
+
+        let binding = Self { buffer, bind_group };
+        binding.write(context, camera)?;
+        Ok(binding)
+    }
+
+    pub(crate) fn write(&self, context: &runtime::RenderContext, camera: &Camera) -> Result<(), RenderError> {
+        let uniform = CameraUniform { view_projection: camera.view_projection() };
+        context.instance.0.queue_write_buffer(context.queue.id, self.buffer.id, 0, bytemuck::bytes_of(&uniform))
+            .map_err(|err| RenderError::resource("Camera buffer", err))
+    }
+}
@@ -0,0 +1,259 @@
+use std::borrow::Cow;
+
+use wgpu::wgc::id::{BindGroupId, BufferId, CommandEncoderId, ComputePipelineId};
+use wgpu::wgt::BufferUsages;
+
+use crate::{error::RenderError, pipeline, runtime, texture::Texture, wgpu_resource::AutoDropId};
+
+/// Converts the colors to their luminance, keeping the alpha.
+pub const GRAYSCALE: &str = r#"
+@group(0) @binding(0) var input: texture_2d<f32>;
+@group(0) @binding(1) var output: texture_storage_2d<rgba8unorm, write>;
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id.xy >= textureDimensions(input))) {
+        return;
+    }
+    let color = textureLoad(input, id.xy, 0);
+    let luminance = dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+    textureStore(output, id.xy, vec4<f32>(vec3<f32>(luminance), color.a));
+}
+"#;
+
+/// A compute shader entry point, with the bind group layouts derived from the shader.
+pub struct ComputePipeline {
+    pipeline: AutoDropId<ComputePipelineId>,
+    workgroup_size: [u32; 3],
+}
+impl ComputePipeline {
+    pub fn new(context: &runtime::RenderContext, label: &str, source: &str, entry_point: &str) -> Result<Self, RenderError> {
+        let desc = wgpu::wgc::pipeline::ShaderModuleDescriptor {
+            label: Some(Cow::Borrowed(label)),
+            runtime_checks: wgpu::wgt::ShaderRuntimeChecks::checked(),
+        };
+        let (shader_id, err) = context.instance.0.device_create_shader_module(context.device.id, &desc, wgpu::wgc::pipeline::ShaderModuleSource::Wgsl(Cow::Borrowed(source)), None);
+        let shader = context.instance.as_auto_drop(shader_id);
+        if let Some(err) = err { return Err(pipeline::shader_error(label.to_string(), err)) }
+
+        let desc = wgpu::wgc::pipeline::ComputePipelineDescriptor {
+            label: Some(Cow::Borrowed(label)),
+            layout: None,
+            stage: wgpu::wgc::pipeline::ProgrammableStageDescriptor {
+                module: shader.id,
+                entry_point: Some(Cow::Borrowed(entry_point)),
+                constants: wgpu::naga::back::PipelineConstants::default(),
+                zero_initialize_workgroup_memory: true,
+            },
+            cache: context.pipeline_cache_id(),
+        };
+        let (pipeline_id, err) = context.instance.0.device_create_compute_pipeline(context.device.id, &desc, None);
+        let pipeline = context.instance.as_auto_drop(pipeline_id);
+        if let Some(err) = err { return Err(RenderError::resource(label.to_string(), err)) }
+
+        // The shader module already validated the source, so it parses again
+        let workgroup_size = wgpu::naga::front::wgsl::parse_str(source).ok()
+            .and_then(|module| module.entry_points.into_iter().find(|entry| entry.name == entry_point))
+            .map(|entry| entry.workgroup_size)
+            .unwrap_or([1, 1, 1])
+        ;
+
+        Ok(Self { pipeline, workgroup_size })
+    }
+
+    pub fn workgroup_size(&self) -> [u32; 3] {
+        self.workgroup_size
+    }
+
+    /// Binds `resources` to the bindings `0..resources.len()` of the bind group `group`.
+    pub fn bind_group(&self, context: &runtime::RenderContext, group: u32, resources: &[ComputeResource]) -> Result<ComputeBindGroup, RenderError> {
+        let (layout_id, err) = context.instance.0.compute_pipeline_get_bind_group_layout(self.pipeline.id, group, None);
+        let layout = context.instance.as_auto_drop(layout_id);
+        if let Some(err) = err { return Err(RenderError::resource("Compute bind group layout", err)) }
+
+        // The views only need to live until the bind group holds them
+        let mut views = Vec::new();
+        let mut entries = Vec::with_capacity(resources.len());
+        for (binding, resource) in (0..).zip(resources) {
+            let resource = match resource {
+                ComputeResource::Texture(texture) => wgpu::wgc::binding_model::BindingResource::TextureView(texture.view.id),
+                ComputeResource::StorageTexture(texture) => {
+                    // Storage bindings hold a single mip level
+                    let view = texture.create_level_view(context, 0).map_err(|err| RenderError::resource("Compute storage view", err))?;
+                    let resource = wgpu::wgc::binding_model::BindingResource::TextureView(view.id);
+                    views.push(view);
+                    resource
+                }
+                ComputeResource::Buffer(buffer) => wgpu::wgc::binding_model::BindingResource::Buffer(wgpu::wgc::binding_model::BufferBinding {
+                    buffer: buffer.buffer.id,
+                    offset: 0,
+                    size: None,
+                }),
+            };
+            entries.push(wgpu::wgc::binding_model::BindGroupEntry { binding, resource });
+        }
+
+        let desc = wgpu::wgc::binding_model::BindGroupDescriptor {
+            label: Some(Cow::Borrowed("Compute bind group")),
+            layout: layout.id,
+            entries: Cow::Owned(entries),
+        };
+        let (group_id, err) = context.instance.0.device_create_bind_group(context.device.id, &desc, None);
+        let bind_group = context.instance.as_auto_drop(group_id);
+        if let Some(err) = err { return Err(RenderError::resource("Compute bind group", err)) }
+
+        Ok(ComputeBindGroup { group, bind_group })
+    }
+}
+
+/// A resource bound to a compute shader.
+pub enum ComputeResource<'a> {
+    /// Read with `textureLoad` from a `texture_2d<f32>`, as linear colors.
+    Texture(&'a Texture),
+    /// The level 0 written through a `texture_storage_2d<rgba8unorm, write>`. The texture must come from `Texture::new_storage`.
+    StorageTexture(&'a Texture),
+    /// A `var<storage>` buffer, read-only or read-write.
+    Buffer(&'a StorageBuffer),
+}
+
+/// Resources bound to one of the groups of a `ComputePipeline`.
+pub struct ComputeBindGroup {
+    group: u32,
+    bind_group: AutoDropId<BindGroupId>,
+}
+
+/// A buffer read and written by compute shaders, which can be read back to CPU memory.
+pub struct StorageBuffer {
+    buffer: AutoDropId<BufferId>,
+    size: u64,
+}
+impl StorageBuffer {
+    pub fn new(context: &runtime::RenderContext, label: &'static str, contents: &[u8]) -> Result<Self, RenderError> {
+        // Mapped buffers must be sized in multiples of `COPY_BUFFER_ALIGNMENT`, and cannot be empty
+        let size = (contents.len() as u64).next_multiple_of(wgpu::wgt::COPY_BUFFER_ALIGNMENT).max(wgpu::wgt::COPY_BUFFER_ALIGNMENT);
+
+        let desc = wgpu::wgt::BufferDescriptor {
+            label: Some(Cow::Borrowed(label)),
+            size,
+            mapped_at_creation: true,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+        };
+        let (buffer_id, err) = context.instance.0.device_create_buffer(context.device.id, &desc, None);
+        let buffer = context.instance.as_auto_drop(buffer_id);
+        if let Some(err) = err { return Err(RenderError::resource(label, err)) }
+
+        let (ptr, _) = context.instance.0.buffer_get_mapped_range(buffer.id, 0, Some(size)).map_err(|err| RenderError::resource(label, err))?;
+        let mapped = unsafe { std::slice::from_raw_parts_mut(ptr.as_ptr(), contents.len()) };
+        mapped.copy_from_slice(contents);
+        context.instance.0.buffer_unmap(buffer.id).map_err(|err| RenderError::resource(label, err))?;
+
+        Ok(Self { buffer, size })
+    }
+
+    /// The size in bytes, rounded up to `COPY_BUFFER_ALIGNMENT`.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Copies the buffer to CPU memory, waiting for the submitted dispatches that write to it.
+    pub fn read(&self, context: &runtime::RenderContext) -> Result<Vec<u8>, RenderError> {
+        let desc = wgpu::wgt::BufferDescriptor {
+            label: Some(Cow::Borrowed("Storage readback buffer")),
+            size: self.size,
+            mapped_at_creation: false,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        };
+        let (readback_id, err) = context.instance.0.device_create_buffer(context.device.id, &desc, None);
+        let readback = context.instance.as_auto_drop(readback_id);
+        if let Some(err) = err { return Err(RenderError::resource("Storage readback buffer", err)) }
+
+        let encoder = context.begin_encode("Begin storage readback")?;
+        context.instance.0.command_encoder_copy_buffer_to_buffer(encoder.id, self.buffer.id, 0, readback.id, 0, Some(self.size))
+            .map_err(|err| RenderError::encode("Storage readback copy", err))?;
+        context.submit(encoder, "Finish storage readback")?;
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let op = wgpu::wgc::resource::BufferMapOperation {
+            host: wgpu::wgc::device::HostMap::Read,
+            callback: Some(Box::new(move |result| { let _ = sender.send(result); })),
+        };
+        context.instance.0.buffer_map_async(readback.id, 0, Some(self.size), op).map_err(RenderError::readback)?;
+        context.instance.0.device_poll(context.device.id, wgpu::wgt::PollType::wait_indefinitely()).map_err(RenderError::readback)?;
+        receiver.recv().map_err(RenderError::readback)?.map_err(RenderError::readback)?;
+
+        let (ptr, _) = context.instance.0.buffer_get_mapped_range(readback.id, 0, Some(self.size)).map_err(RenderError::readback)?;
+        let bytes = unsafe { std::slice::from_raw_parts(ptr.as_ptr(), self.size as usize) }.to_vec();
+        context.instance.0.buffer_unmap(readback.id).map_err(RenderError::readback)?;
+
+        Ok(bytes)
+    }
+}
+
+/// Records compute dispatches, each in its own pass, until they are submitted together.
+pub struct ComputeEncoder<'a> {
+    context: &'a runtime::RenderContext,
+    encoder: AutoDropId<CommandEncoderId>,
+}
+impl<'a> ComputeEncoder<'a> {
+    pub fn new(context: &'a runtime::RenderContext) -> Result<Self, RenderError> {
+        let encoder = context.begin_encode("Begin compute")?;
+        Ok(Self { context, encoder })
+    }
+
+    /// Dispatches `workgroups` workgroups of the pipeline.
+    pub fn dispatch(&mut self, pipeline: &ComputePipeline, bind_groups: &[&ComputeBindGroup], [x, y, z]: [u32; 3]) -> Result<(), RenderError> {
+        let instance = &self.context.instance.0;
+
+        let desc = wgpu::wgc::command::ComputePassDescriptor { label: Some(Cow::Borrowed("Compute pass")), timestamp_writes: None };
+        let (mut pass, err) = instance.command_encoder_begin_compute_pass(self.encoder.id, &desc);
+        if let Some(err) = err { return Err(RenderError::encode("Compute pass", err)) }
+
+        instance.compute_pass_set_pipeline(&mut pass, pipeline.pipeline.id).map_err(|err| RenderError::encode("Compute pass", err))?;
+        for bind_group in bind_groups {
+            instance.compute_pass_set_bind_group(&mut pass, bind_group.group, Some(bind_group.bind_group.id), &[])
+                .map_err(|err| RenderError::encode("Compute pass", err))?;
+        }
+        instance.compute_pass_dispatch_workgroups(&mut pass, x, y, z).map_err(|err| RenderError::encode("Compute pass", err))?;
+        instance.compute_pass_end(&mut pass).map_err(|err| RenderError::encode("Compute pass", err))?;
+
+        Ok(())
+    }
+
+    /// Dispatches enough workgroups to run at least `invocations` invocations of the pipeline.
+    /// The shader must skip the invocations out of range, as the last workgroups may overrun.
+    pub fn dispatch_for(&mut self, pipeline: &ComputePipeline, bind_groups: &[&ComputeBindGroup], invocations: [u32; 3]) -> Result<(), RenderError> {
+        let workgroups = std::array::from_fn(|i| invocations[i].div_ceil(pipeline.workgroup_size[i]));
+        self.dispatch(pipeline, bind_groups, workgroups)
+    }
+
+    pub fn submit(self) -> Result<(), RenderError> {
+        self.context.submit(self.encoder, "Finish compute")
+    }
+}
+
+/// An image processing pass from a compute shader, writing a new texture from a loaded one before it is sampled.
+///
+/// The shader has an entry point `main`, and binds the input as `texture_2d<f32>` at binding 0 and
+/// the output as `texture_storage_2d<rgba8unorm, write>` at binding 1 of group 0, e.g. `GRAYSCALE`.
+/// Both hold linear colors: the sRGB texels of loaded images are decoded when they are loaded.
+pub struct ImageFilter {
+    pipeline: ComputePipeline,
+}
+impl ImageFilter {
+    pub fn new(context: &runtime::RenderContext, label: &str, source: &str) -> Result<Self, RenderError> {
+        Ok(Self { pipeline: ComputePipeline::new(context, label, source, "main")? })
+    }
+
+    pub fn apply(&self, context: &runtime::RenderContext, input: &Texture) -> Result<Texture, RenderError> {
+        let (width, height) = input.size();
+        let output = Texture::new_storage(context, (width, height)).map_err(|err| RenderError::resource("Filtered texture", err))?;
+        let bind_group = self.pipeline.bind_group(context, 0, &[ComputeResource::Texture(input), ComputeResource::StorageTexture(&output)])?;
+
+        let mut encoder = ComputeEncoder::new(context)?;
+        encoder.dispatch_for(&self.pipeline, &[&bind_group], [width, height, 1])?;
+        encoder.submit()?;
+        output.generate_mipmaps(context)?;
+
+        Ok(output)
+    }
+}
@@ -0,0 +1,88 @@
+use std::borrow::Cow;
+
+type Cause = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Errors raised while creating the rendering context or drawing frames.
+#[derive(Debug)]
+pub enum RenderError {
+    /// No adapter, device or queue could be obtained.
+    Device(Cause),
+    /// The window surface could not be created, queried or presented.
+    Surface(Cause),
+    /// The next surface texture could not be acquired.
+    Acquire(wgpu::wgt::SurfaceStatus),
+    /// The shader failed to parse or validate.
+    Shader { label: Cow<'static, str>, location: Option<(u32, u32)>, source: Cause },
+    /// wgpu-core rejected to create a resource.
+    Resource { label: Cow<'static, str>, source: Cause },
+    /// Recording commands into an encoder or a pass failed.
+    Encode { label: Cow<'static, str>, source: Cause },
+    /// Submitting command buffers to the queue failed.
+    Submit(Cause),
+    /// Reading a texture or a buffer back to CPU memory failed.
+    Readback(Cause),
+    /// The device has been lost, the rendering context must be recreated.
+    DeviceLost,
+}
+
+impl RenderError {
+    pub(crate) fn device(err: impl Into<Cause>) -> Self {
+        RenderError::Device(err.into())
+    }
+
+    pub(crate) fn surface(err: impl Into<Cause>) -> Self {
+        RenderError::Surface(err.into())
+    }
+
+    pub(crate) fn resource(label: impl Into<Cow<'static, str>>, err: impl Into<Cause>) -> Self {
+        RenderError::Resource { label: label.into(), source: err.into() }
+    }
+
+    pub(crate) fn encode(label: impl Into<Cow<'static, str>>, err: impl Into<Cause>) -> Self {
+        RenderError::Encode { label: label.into(), source: err.into() }
+    }
+
+    pub(crate) fn submit(err: impl Into<Cause>) -> Self {
+        RenderError::Submit(err.into())
+    }
+
+    pub(crate) fn readback(err: impl Into<Cause>) -> Self {
+        RenderError::Readback(err.into())
+    }
+
+    /// Returns true when the error only affects the current frame, so the caller can simply try the next one.
+    /// A lost device is included, as frames are skipped until the context is recreated.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(self,
+            RenderError::Acquire(wgpu::wgt::SurfaceStatus::Timeout | wgpu::wgt::SurfaceStatus::Outdated | wgpu::wgt::SurfaceStatus::Lost) |
+            RenderError::DeviceLost
+        )
+    }
+}
+
+impl std::fmt::Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderError::Device(err) => write!(f, "Failed to create the device (cause: {err})"),
+            RenderError::Surface(err) => write!(f, "Surface error (cause: {err})"),
+            RenderError::Acquire(status) => write!(f, "Failed to acquire the surface texture (status: {status:?})"),
+            RenderError::Shader { label, location: Some((line, column)), source } => write!(f, "Shader `{label}` is invalid at line {line}, column {column} (cause: {source})"),
+            RenderError::Shader { label, location: None, source } => write!(f, "Shader `{label}` is invalid (cause: {source})"),
+            RenderError::Resource { label, source } => write!(f, "Failed to create `{label}` (cause: {source})"),
+            RenderError::Encode { label, source } => write!(f, "Failed to encode `{label}` (cause: {source})"),
+            RenderError::Submit(err) => write!(f, "Failed to submit commands (cause: {err})"),
+            RenderError::Readback(err) => write!(f, "Failed to read back from the GPU (cause: {err})"),
+            RenderError::DeviceLost => write!(f, "The device is lost"),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RenderError::Acquire(_) | RenderError::DeviceLost => None,
+            RenderError::Device(err) | RenderError::Surface(err) | RenderError::Submit(err) | RenderError::Readback(err) => Some(err.as_ref()),
+            RenderError::Shader { source, .. } | RenderError::Resource { source, .. } | RenderError::Encode { source, .. } => Some(source.as_ref()),
+        }
+    }
+}
@@ -3,27 +3,49 @@ use std::borrow::Cow;
 use winit::event_loop::EventLoop;
 pub mod app;
 
-mod runtime;
-mod render;
+pub mod camera;
+pub mod compute;
+pub mod error;
+pub mod mesh;
+pub mod obj;
+pub mod pipeline;
+pub mod runtime;
+pub mod render;
+pub mod scene;
+pub mod surface;
+pub mod texture;
+pub mod window;
+mod mipmap;
+mod readback;
+mod shader_watch;
 mod wgpu_resource;
 
 pub async fn run() -> Result<(), anyhow::Error> {
     let event_loop = EventLoop::with_user_event().build()?;
     let mut app = app::App::new(&event_loop, true);
+    if let Some(path) = std::env::var_os("SHADER_WATCH") {
+        app.watch_shader(path);
+    }
+    if std::env::var_os("HDR").is_some() {
+        let options = runtime::RenderOptions { hdr: true, ..app.render_options().clone() };
+        app.set_render_options(options);
+    }
 
     event_loop.run_app(&mut app)?;
     Ok(())
 }
 
+/// A vertex in the layout read by the render pipeline.
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::NoUninit, bytemuck::Zeroable)]
-struct Vertex {
-    position: [f32; 3],
-    texture_coords: [f32; 2],
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub texture_coords: [f32; 2],
+    pub normal: [f32; 3],
 }
 impl Vertex {
-    fn desc() -> wgpu::wgc::pipeline::VertexBufferLayout<'static> {
-        wgpu::wgc::pipeline::VertexBufferLayout {
+    pub fn layout() -> pipeline::VertexLayout {
+        pipeline::VertexLayout {
             array_stride: size_of::<Self>() as wgpu::wgt::BufferAddress,
             step_mode: wgpu::wgt::VertexStepMode::Vertex,
             attributes: Cow::Borrowed(&[
@@ -37,17 +59,22 @@ impl Vertex {
                     offset: std::mem::offset_of!(Self, texture_coords) as wgpu::wgt::BufferAddress,
                     shader_location: 1,
                 },
+                wgpu::wgt::VertexAttribute {
+                    format: wgpu::wgt::VertexFormat::Float32x3,
+                    offset: std::mem::offset_of!(Self, normal) as wgpu::wgt::BufferAddress,
+                    shader_location: 2,
+                },
             ]),
         }
     }
 }
 
 const VERTICES: &[Vertex] = &[
-    Vertex { position: [-0.0868241, 0.49240386, 0.0], texture_coords: [0.4131759, 0.00759614], }, // A
-    Vertex { position: [-0.49513406, 0.06958647, 0.0], texture_coords: [0.0048659444, 0.43041354], }, // B
-    Vertex { position: [-0.21918549, -0.44939706, 0.0], texture_coords: [0.28081453, 0.949397], }, // C
-    Vertex { position: [0.35966998, -0.3473291, 0.0], texture_coords: [0.85967, 0.84732914], }, // D
-    Vertex { position: [0.44147372, 0.2347359, 0.0], texture_coords: [0.9414737, 0.2652641], }, // E
+    Vertex { position: [-0.0868241, 0.49240386, 0.0], texture_coords: [0.4131759, 0.00759614], normal: [0.0, 0.0, 1.0], }, // A
+    Vertex { position: [-0.49513406, 0.06958647, 0.0], texture_coords: [0.0048659444, 0.43041354], normal: [0.0, 0.0, 1.0], }, // B
+    Vertex { position: [-0.21918549, -0.44939706, 0.0], texture_coords: [0.28081453, 0.949397], normal: [0.0, 0.0, 1.0], }, // C
+    Vertex { position: [0.35966998, -0.3473291, 0.0], texture_coords: [0.85967, 0.84732914], normal: [0.0, 0.0, 1.0], }, // D
+    Vertex { position: [0.44147372, 0.2347359, 0.0], texture_coords: [0.9414737, 0.2652641], normal: [0.0, 0.0, 1.0], }, // E
 ];
 
 const INDICES: &[u32] = &[
@@ -0,0 +1,285 @@
+use std::borrow::Cow;
+
+use wgpu::wgc::id::BufferId;
+use wgpu::wgt::BufferUsages;
+
+use crate::{Vertex, error::RenderError, pipeline::PipelineKey, runtime, wgpu_resource::AutoDropId};
+
+/// Indices into the vertices of a mesh, either 16 or 32 bits wide.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+impl Indices {
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn format(&self) -> wgpu::wgt::IndexFormat {
+        match self {
+            Indices::U16(_) => wgpu::wgt::IndexFormat::Uint16,
+            Indices::U32(_) => wgpu::wgt::IndexFormat::Uint32,
+        }
+    }
+
+    /// The largest index, which must be lower than the vertex count.
+    pub fn max(&self) -> Option<u32> {
+        match self {
+            Indices::U16(indices) => indices.iter().max().map(|&i| i as u32),
+            Indices::U32(indices) => indices.iter().max().copied(),
+        }
+    }
+
+    /// Widens the indices to 32 bits.
+    pub fn to_u32(&self) -> Vec<u32> {
+        match self {
+            Indices::U16(indices) => indices.iter().map(|&i| i as u32).collect(),
+            Indices::U32(indices) => indices.clone(),
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Indices::U16(indices) => bytemuck::cast_slice(indices),
+            Indices::U32(indices) => bytemuck::cast_slice(indices),
+        }
+    }
+}
+
+impl From<Vec<u16>> for Indices {
+    fn from(indices: Vec<u16>) -> Self {
+        Indices::U16(indices)
+    }
+}
+
+impl From<Vec<u32>> for Indices {
+    fn from(indices: Vec<u32>) -> Self {
+        Indices::U32(indices)
+    }
+}
+
+impl From<&[u16]> for Indices {
+    fn from(indices: &[u16]) -> Self {
+        Indices::U16(indices.to_vec())
+    }
+}
+
+impl From<&[u32]> for Indices {
+    fn from(indices: &[u32]) -> Self {
+        Indices::U32(indices.to_vec())
+    }
+}
+
+/// Geometry on the CPU side, drawn as an indexed triangle list.
+#[derive(Clone, Debug)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Indices,
+}
+impl MeshData {
+    pub fn new(vertices: Vec<Vertex>, indices: impl Into<Indices>) -> Self {
+        Self { vertices, indices: indices.into() }
+    }
+
+    /// The textured pentagon shown by the demo.
+    pub fn pentagon() -> Self {
+        Self::new(crate::VERTICES.to_vec(), crate::INDICES)
+    }
+
+    /// Replaces the normals by the average of the adjacent faces. The indices must be in range of the vertices.
+    pub fn compute_normals(&mut self) {
+        for vertex in &mut self.vertices {
+            vertex.normal = [0.0, 0.0, 0.0];
+        }
+
+        let indices = self.indices.to_u32();
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| self.vertices[triangle[i] as usize].position);
+            let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+            let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+            // Not normalized, so that larger faces weigh more
+            let face = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+
+            for &i in triangle {
+                let normal = &mut self.vertices[i as usize].normal;
+                for axis in 0..3 {
+                    normal[axis] += face[axis];
+                }
+            }
+        }
+
+        for vertex in &mut self.vertices {
+            vertex.normal = normalize(vertex.normal).unwrap_or([0.0, 0.0, 1.0]);
+        }
+    }
+}
+
+/// Vertex and index buffers resident on the GPU.
+/// The data is uploaded once at creation, later changes are uploaded by `flush` only when the mesh is dirty.
+pub struct Mesh {
+    vertex_buffer: MeshBuffer,
+    index_buffer: MeshBuffer,
+    data: MeshData,
+    /// Overrides the pipeline of the renderer drawing this mesh.
+    pipeline: Option<PipelineKey>,
+    dirty_vertices: bool,
+    dirty_indices: bool,
+}
+impl Mesh {
+    pub fn new(context: &runtime::RenderContext, data: MeshData) -> Result<Self, RenderError> {
+        let vertex_buffer = MeshBuffer::new(context, "Vertex buffer", BufferUsages::VERTEX, bytemuck::cast_slice(&data.vertices))?;
+        let index_buffer = MeshBuffer::new(context, "Index buffer", BufferUsages::INDEX, data.indices.as_bytes())?;
+
+        Ok(Self {
+            vertex_buffer,
+            index_buffer,
+            data,
+            pipeline: None,
+            dirty_vertices: false,
+            dirty_indices: false,
+        })
+    }
+
+    /// Uploads the same data through another context, e.g. after the device has been lost.
+    pub(crate) fn recreate(&self, context: &runtime::RenderContext) -> Result<Self, RenderError> {
+        let mut mesh = Self::new(context, self.data.clone())?;
+        mesh.pipeline = self.pipeline.clone();
+        Ok(mesh)
+    }
+
+    pub fn data(&self) -> &MeshData {
+        &self.data
+    }
+
+    pub fn set_vertices(&mut self, vertices: &[Vertex]) {
+        self.data.vertices.clear();
+        self.data.vertices.extend_from_slice(vertices);
+        self.dirty_vertices = true;
+    }
+
+    /// Replaces the indices. The index format may change as well.
+    pub fn set_indices(&mut self, indices: impl Into<Indices>) {
+        self.data.indices = indices.into();
+        self.dirty_indices = true;
+    }
+
+    /// Forces the whole mesh to be uploaded again on the next `flush`.
+    pub fn mark_dirty(&mut self) {
+        self.dirty_vertices = true;
+        self.dirty_indices = true;
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty_vertices || self.dirty_indices
+    }
+
+    pub fn pipeline(&self) -> Option<&PipelineKey> {
+        self.pipeline.as_ref()
+    }
+
+    /// Draws this mesh with the pipeline of `key` instead of the one of the renderer. `None` restores the latter.
+    pub fn set_pipeline(&mut self, key: Option<PipelineKey>) {
+        self.pipeline = key;
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.data.indices.len() as u32
+    }
+
+    pub fn index_format(&self) -> wgpu::wgt::IndexFormat {
+        self.data.indices.format()
+    }
+
+    pub(crate) fn vertex_buffer_id(&self) -> BufferId {
+        self.vertex_buffer.buffer.id
+    }
+
+    pub(crate) fn index_buffer_id(&self) -> BufferId {
+        self.index_buffer.buffer.id
+    }
+
+    /// Uploads the modified data. Buffers are reallocated only when the data has outgrown them.
+    pub(crate) fn flush(&mut self, context: &runtime::RenderContext) -> Result<(), RenderError> {
+        if self.dirty_vertices {
+            self.vertex_buffer.write(context, bytemuck::cast_slice(&self.data.vertices))?;
+            self.dirty_vertices = false;
+        }
+        if self.dirty_indices {
+            self.index_buffer.write(context, self.data.indices.as_bytes())?;
+            self.dirty_indices = false;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn normalize([x, y, z]: [f32; 3]) -> Option<[f32; 3]> {
+    let length = (x * x + y * y + z * z).sqrt();
+    (length > f32::EPSILON).then(|| [x / length, y / length, z / length])
+}
+
+struct MeshBuffer {
+    buffer: AutoDropId<BufferId>,
+    label: &'static str,
+    usage: BufferUsages,
+    capacity: u64,
+}
+impl MeshBuffer {
+    /// Creates a buffer filled with `contents` through a mapping at creation, so no staging copy is submitted.
+    fn new(context: &runtime::RenderContext, label: &'static str, usage: BufferUsages, contents: &[u8]) -> Result<Self, RenderError> {
+        // Mapped buffers must be sized in multiples of `COPY_BUFFER_ALIGNMENT`, and cannot be empty
+        let capacity = aligned_size(contents).max(wgpu::wgt::COPY_BUFFER_ALIGNMENT);
+
+        let desc = wgpu::wgt::BufferDescriptor {
+            label: Some(Cow::Borrowed(label)),
+            size: capacity,
+            mapped_at_creation: true,
+            // COPY_DST is kept for the dirty updates
+            usage: usage | BufferUsages::COPY_DST,
+        };
+        let (buffer_id, err) = context.instance.0.device_create_buffer(context.device.id, &desc, None);
+        let buffer = context.instance.as_auto_drop(buffer_id);
+        if let Some(err) = err { return Err(RenderError::resource(label, err)) }
+
+        let (ptr, _) = context.instance.0.buffer_get_mapped_range(buffer.id, 0, Some(capacity)).map_err(|err| RenderError::resource(label, err))?;
+        let mapped = unsafe { std::slice::from_raw_parts_mut(ptr.as_ptr(), contents.len()) };
+        mapped.copy_from_slice(contents);
+        context.instance.0.buffer_unmap(buffer.id).map_err(|err| RenderError::resource(label, err))?;
+
+        Ok(Self { buffer, label, usage, capacity })
+    }
+
+    /// Overwrites the buffer, reallocating it when `contents` does not fit.
+    fn write(&mut self, context: &runtime::RenderContext, contents: &[u8]) -> Result<(), RenderError> {
+        let size = aligned_size(contents);
+
+        if size > self.capacity {
+            *self = Self::new(context, self.label, self.usage, contents)?;
+            return Ok(())
+        }
+        if contents.is_empty() { return Ok(()) }
+
+        // Queue writes must be sized in multiples of `COPY_BUFFER_ALIGNMENT` as well
+        let contents = match contents.len() as u64 == size {
+            true => Cow::Borrowed(contents),
+            false => {
+                let mut padded = contents.to_vec();
+                padded.resize(size as usize, 0);
+                Cow::Owned(padded)
+            }
+        };
+        context.instance.0.queue_write_buffer(context.queue.id, self.buffer.id, 0, &contents)
+            .map_err(|err| RenderError::resource(self.label, err))
+    }
+}
+
+fn aligned_size(contents: &[u8]) -> u64 {
+    (contents.len() as u64).next_multiple_of(wgpu::wgt::COPY_BUFFER_ALIGNMENT)
+}
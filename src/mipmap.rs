@@ -0,0 +1,180 @@
+use std::{borrow::Cow, collections::HashMap, sync::{Arc, Mutex}};
+
+use wgpu::wgc::id::RenderPipelineId;
+use wgpu::wgt::TextureFormat;
+
+use crate::{error::RenderError, pipeline, runtime, texture::Texture, wgpu_resource::AutoDropId};
+
+/// Draws a level from the previous one, which the sampler filters linearly.
+const DOWNSAMPLE: &str = r#"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// A triangle covering the viewport, without vertex buffer
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(source, source_sampler, in.uv);
+}
+"#;
+
+/// Number of levels down to 1x1 for a texture of `size`.
+pub(crate) fn full_mip_level_count((width, height): (u32, u32)) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
+}
+
+/// Fills the mip chains of textures, with a downsampling pipeline built once for each format.
+#[derive(Default)]
+pub(crate) struct MipmapGenerator {
+    pipelines: Mutex<HashMap<TextureFormat, Arc<AutoDropId<RenderPipelineId>>>>,
+}
+impl MipmapGenerator {
+    /// Renders each level of `texture` from the previous one, starting from the level 0.
+    pub(crate) fn generate(&self, context: &runtime::RenderContext, texture: &Texture) -> Result<(), RenderError> {
+        if texture.mip_level_count() <= 1 { return Ok(()) }
+
+        let pipeline = self.pipeline(context, texture.format())?;
+        let (layout_id, err) = context.instance.0.render_pipeline_get_bind_group_layout(pipeline.id, 0, None);
+        let layout = context.instance.as_auto_drop(layout_id);
+        if let Some(err) = err { return Err(RenderError::resource("Mipmap bind group layout", err)) }
+
+        let desc = wgpu::wgc::resource::SamplerDescriptor {
+            label: Some(Cow::Borrowed("Mipmap sampler")),
+            address_modes: [wgpu::wgt::AddressMode::ClampToEdge; 3],
+            mag_filter: wgpu::wgt::FilterMode::Linear,
+            min_filter: wgpu::wgt::FilterMode::Linear,
+            mipmap_filter: wgpu::wgt::MipmapFilterMode::Nearest,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 32.0,
+            compare: None,
+            anisotropy_clamp: 1,
+            border_color: None,
+        };
+        let (sampler_id, err) = context.instance.0.device_create_sampler(context.device.id, &desc, None);
+        let sampler = context.instance.as_auto_drop(sampler_id);
+        if let Some(err) = err { return Err(RenderError::resource("Mipmap sampler", err)) }
+
+        let views = (0..texture.mip_level_count())
+            .map(|level| texture.create_level_view(context, level).map_err(|err| RenderError::resource("Mipmap view", err)))
+            .collect::<Result<Vec<_>, _>>()?
+        ;
+
+        let encoder = context.begin_encode("Begin mipmaps")?;
+        for pair in views.windows(2) {
+            let [source, target] = pair else { unreachable!() };
+
+            let desc = wgpu::wgc::binding_model::BindGroupDescriptor {
+                label: Some(Cow::Borrowed("Mipmap bind group")),
+                layout: layout.id,
+                entries: Cow::Borrowed(&[
+                    wgpu::wgc::binding_model::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::wgc::binding_model::BindingResource::TextureView(source.id),
+                    },
+                    wgpu::wgc::binding_model::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::wgc::binding_model::BindingResource::Sampler(sampler.id),
+                    },
+                ]),
+            };
+            let (group_id, err) = context.instance.0.device_create_bind_group(context.device.id, &desc, None);
+            let bind_group = context.instance.as_auto_drop(group_id);
+            if let Some(err) = err { return Err(RenderError::resource("Mipmap bind group", err)) }
+
+            let desc = wgpu::wgc::command::RenderPassDescriptor {
+                label: Some(Cow::Borrowed("Mipmap pass")),
+                color_attachments: Cow::Borrowed(&[
+                    Some(wgpu::wgc::command::RenderPassColorAttachment {
+                        view: target.id,
+                        depth_slice: None,
+                        resolve_target: None,
+                        load_op: wgpu::wgc::command::LoadOp::Clear(wgpu::wgt::Color::TRANSPARENT),
+                        store_op: wgpu::wgc::command::StoreOp::Store,
+                    })
+                ]),
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            };
+
+            let pass_error = |err: wgpu::wgc::command::PassStateError| RenderError::encode("Mipmap pass", err);
+            let (mut pass, err) = context.instance.0.command_encoder_begin_render_pass(encoder.id, &desc);
+            if let Some(err) = err { return Err(RenderError::encode("Mipmap pass", err)) }
+            context.instance.0.render_pass_set_pipeline(&mut pass, pipeline.id).map_err(pass_error)?;
+            context.instance.0.render_pass_set_bind_group(&mut pass, 0, Some(bind_group.id), &[]).map_err(pass_error)?;
+            context.instance.0.render_pass_draw(&mut pass, 3, 1, 0, 0).map_err(pass_error)?;
+            context.instance.0.render_pass_end(&mut pass).map_err(|err| RenderError::encode("Mipmap pass", err))?;
+        }
+        context.submit(encoder, "Finish mipmaps")
+    }
+
+    fn pipeline(&self, context: &runtime::RenderContext, format: TextureFormat) -> Result<Arc<AutoDropId<RenderPipelineId>>, RenderError> {
+        let mut pipelines = self.pipelines.lock().unwrap();
+        if let Some(pipeline) = pipelines.get(&format) {
+            return Ok(pipeline.clone())
+        }
+
+        let source = wgpu::wgc::pipeline::ShaderModuleSource::Wgsl(Cow::Borrowed(DOWNSAMPLE));
+        let desc = wgpu::wgc::pipeline::ShaderModuleDescriptor {
+            label: Some(Cow::Borrowed("Mipmap shader")),
+            runtime_checks: wgpu::wgt::ShaderRuntimeChecks::checked(),
+        };
+        let (shader_id, err) = context.instance.0.device_create_shader_module(context.device.id, &desc, source, None);
+        let shader = context.instance.as_auto_drop(shader_id);
+        if let Some(err) = err { return Err(pipeline::shader_error("Mipmap shader".to_string(), err)) }
+
+        let desc = wgpu::wgc::pipeline::RenderPipelineDescriptor {
+            label: Some(Cow::Borrowed("Mipmap pipeline")),
+            layout: None,
+            vertex: wgpu::wgc::pipeline::VertexState {
+                stage: wgpu::wgc::pipeline::ProgrammableStageDescriptor {
+                    module: shader.id,
+                    entry_point: Some(Cow::Borrowed("vs_main")),
+                    constants: wgpu::naga::back::PipelineConstants::default(),
+                    zero_initialize_workgroup_memory: false,
+                },
+                buffers: Cow::Borrowed(&[]),
+            },
+            fragment: Some(wgpu::wgc::pipeline::FragmentState {
+                stage: wgpu::wgc::pipeline::ProgrammableStageDescriptor {
+                    module: shader.id,
+                    entry_point: Some(Cow::Borrowed("fs_main")),
+                    constants: wgpu::naga::back::PipelineConstants::default(),
+                    zero_initialize_workgroup_memory: false,
+                },
+                targets: Cow::Owned(vec![
+                    Some(wgpu::wgt::ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: wgpu::wgt::ColorWrites::ALL,
+                    })
+                ]),
+            }),
+            primitive: wgpu::wgt::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::wgt::MultisampleState::default(),
+            multiview_mask: None,
+            cache: context.pipeline_cache_id(),
+        };
+        let (pipeline_id, err) = context.instance.0.device_create_render_pipeline(context.device.id, &desc, None);
+        let pipeline = Arc::new(context.instance.as_auto_drop(pipeline_id));
+        if let Some(err) = err { return Err(RenderError::resource("Mipmap pipeline", err)) }
+
+        pipelines.insert(format, pipeline.clone());
+        Ok(pipeline)
+    }
+}
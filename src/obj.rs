@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+
+use crate::{Vertex, mesh::{Indices, MeshData}};
+
+#[derive(Debug)]
+pub enum ObjError {
+    /// The model file could not be read.
+    Io { path: PathBuf, source: std::io::Error },
+    /// The model file is not a valid OBJ.
+    Parse(tobj::LoadError),
+    /// A face refers to a vertex beyond `vertex_count`.
+    IndexOutOfRange { index: u32, vertex_count: usize },
+    /// The model does not contain any face.
+    Empty,
+}
+
+impl std::fmt::Display for ObjError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "Failed to read {} (cause: {source})", path.display()),
+            ObjError::Parse(err) => write!(f, "Failed to parse OBJ model (cause: {err})"),
+            ObjError::IndexOutOfRange { index, vertex_count } => write!(f, "OBJ face refers to the vertex {index} out of {vertex_count}"),
+            ObjError::Empty => write!(f, "OBJ model has no faces"),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse(err) => Some(err),
+            ObjError::IndexOutOfRange { .. } | ObjError::Empty => None,
+        }
+    }
+}
+
+/// An object or group of an OBJ model.
+#[derive(Clone, Debug)]
+pub struct ObjGroup {
+    pub name: String,
+    pub data: MeshData,
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<ObjGroup>, ObjError> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).map_err(|source| ObjError::Io { path: path.to_path_buf(), source })?;
+
+    load_from_bytes(&bytes)
+}
+
+/// Parses an OBJ model into one mesh per object or group. Faces are triangulated.
+/// Materials are not loaded, missing normals are computed by averaging the adjacent faces.
+pub fn load_from_bytes(bytes: &[u8]) -> Result<Vec<ObjGroup>, ObjError> {
+    let (models, _) = tobj::load_obj_buf(&mut std::io::Cursor::new(bytes), &tobj::GPU_LOAD_OPTIONS, |_| Ok(Default::default()))
+        .map_err(ObjError::Parse)?;
+
+    let groups = models.into_iter()
+        .filter(|model| ! model.mesh.indices.is_empty())
+        .map(|model| Ok(ObjGroup { name: model.name, data: to_mesh_data(&model.mesh)? }))
+        .collect::<Result<Vec<_>, _>>()?
+    ;
+    if groups.is_empty() { return Err(ObjError::Empty) }
+
+    Ok(groups)
+}
+
+fn to_mesh_data(mesh: &tobj::Mesh) -> Result<MeshData, ObjError> {
+    let vertex_count = mesh.positions.len() / 3;
+    let has_normals = mesh.normals.len() == mesh.positions.len();
+    let has_texture_coords = mesh.texcoords.len() / 2 == vertex_count;
+
+    let vertices = (0..vertex_count)
+        .map(|i| Vertex {
+            position: [mesh.positions[3 * i], mesh.positions[3 * i + 1], mesh.positions[3 * i + 2]],
+            // OBJ puts the origin of the texture at the bottom left, while wgpu puts it at the top left
+            texture_coords: match has_texture_coords {
+                true => [mesh.texcoords[2 * i], 1.0 - mesh.texcoords[2 * i + 1]],
+                false => [0.0, 0.0],
+            },
+            normal: match has_normals {
+                true => [mesh.normals[3 * i], mesh.normals[3 * i + 1], mesh.normals[3 * i + 2]],
+                false => [0.0, 0.0, 0.0],
+            },
+        })
+        .collect::<Vec<_>>()
+    ;
+    // `compute_normals` must not see an index out of range
+    if let Some(&index) = mesh.indices.iter().find(|&&index| index as usize >= vertex_count) {
+        return Err(ObjError::IndexOutOfRange { index, vertex_count })
+    }
+    let indices = match u16::try_from(vertex_count) {
+        Ok(_) => Indices::U16(mesh.indices.iter().map(|&i| i as u16).collect()),
+        Err(_) => Indices::U32(mesh.indices.clone()),
+    };
+
+    let mut data = MeshData { vertices, indices };
+    if ! has_normals {
+        data.compute_normals();
+    }
+    Ok(data)
+}
@@ -0,0 +1,336 @@
+use std::{borrow::Cow, collections::HashMap, path::{Path, PathBuf}, sync::{Arc, RwLock}};
+
+use wgpu::wgc::id::{DeviceId, PipelineCacheId, PipelineLayoutId, RenderPipelineId};
+
+use crate::{error::RenderError, runtime::RenderOptions, wgpu_resource::{AutoDropId, WgpuInstance}};
+
+/// Name under which the built-in shader is registered, replaced by `RenderContext::reload_shader`.
+pub const DEFAULT_SHADER: &str = "default";
+
+/// Layout of the vertex buffer read by a pipeline.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VertexLayout {
+    pub array_stride: wgpu::wgt::BufferAddress,
+    pub step_mode: wgpu::wgt::VertexStepMode,
+    pub attributes: Cow<'static, [wgpu::wgt::VertexAttribute]>,
+}
+impl VertexLayout {
+    fn desc(&self) -> wgpu::wgc::pipeline::VertexBufferLayout<'_> {
+        wgpu::wgc::pipeline::VertexBufferLayout {
+            array_stride: self.array_stride,
+            step_mode: self.step_mode,
+            attributes: Cow::Borrowed(&self.attributes),
+        }
+    }
+}
+
+/// The state a render pipeline is built from. Draws with equal keys share the same pipeline.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    /// Name of a shader registered with `RenderContext::register_shader`.
+    pub shader: Cow<'static, str>,
+    pub vertex_layout: VertexLayout,
+    pub blend: Option<wgpu::wgt::BlendState>,
+    pub topology: wgpu::wgt::PrimitiveTopology,
+    pub cull_mode: Option<wgpu::wgt::Face>,
+    /// Modes other than `Fill` fall back to it when the device lacks the matching feature.
+    pub polygon_mode: wgpu::wgt::PolygonMode,
+    /// Test against the depth of what is drawn before, where the context has a depth buffer.
+    /// `None` draws over everything and leaves the depth untouched.
+    pub depth_compare: Option<wgpu::wgt::CompareFunction>,
+    /// Format of the color target. Renderers build the pipeline for the format of their target when it differs.
+    pub format: wgpu::wgt::TextureFormat,
+}
+impl PipelineKey {
+    /// Opaque triangles of `Vertex` drawn by the built-in shader, with the back faces culled.
+    pub fn new(format: wgpu::wgt::TextureFormat) -> Self {
+        Self {
+            shader: Cow::Borrowed(DEFAULT_SHADER),
+            vertex_layout: crate::Vertex::layout(),
+            blend: Some(wgpu::wgt::BlendState::REPLACE),
+            topology: wgpu::wgt::PrimitiveTopology::TriangleList,
+            cull_mode: Some(wgpu::wgt::Face::Back),
+            polygon_mode: wgpu::wgt::PolygonMode::Fill,
+            depth_compare: Some(wgpu::wgt::CompareFunction::Less),
+            format,
+        }
+    }
+
+    /// Blends with what is behind by the alpha of the fragments, e.g. for sprites.
+    pub fn alpha_blended(mut self) -> Self {
+        self.blend = Some(wgpu::wgt::BlendState::ALPHA_BLENDING);
+        self
+    }
+
+    /// Draws the back faces as well.
+    pub fn double_sided(mut self) -> Self {
+        self.cull_mode = None;
+        self
+    }
+
+    /// Draws every pair of indices as a line, e.g. for debug overlays.
+    pub fn lines(mut self) -> Self {
+        self.topology = wgpu::wgt::PrimitiveTopology::LineList;
+        self.cull_mode = None;
+        self
+    }
+
+    /// Draws only the edges of the triangles. Needs `Features::POLYGON_MODE_LINE`, filled otherwise.
+    pub fn wireframe(mut self) -> Self {
+        self.polygon_mode = wgpu::wgt::PolygonMode::Line;
+        self
+    }
+
+    /// Skips the depth test, e.g. for debug overlays which stay visible through the meshes.
+    pub fn without_depth(mut self) -> Self {
+        self.depth_compare = None;
+        self
+    }
+
+    /// Opaque pipelines write the depth, while blended ones only test it so that everything behind them stays visible.
+    pub fn is_opaque(&self) -> bool {
+        matches!(self.blend, None | Some(wgpu::wgt::BlendState::REPLACE))
+    }
+}
+
+type SharedPipeline = Arc<AutoDropId<RenderPipelineId>>;
+
+/// Shader sources by name, and the pipelines built from them so far.
+/// Pipelines are made on first use and kept until their shader is replaced.
+pub(crate) struct PipelineRegistry {
+    shaders: RwLock<HashMap<Cow<'static, str>, Cow<'static, str>>>,
+    pipelines: RwLock<HashMap<PipelineKey, SharedPipeline>>,
+    cache: Option<PipelineCache>,
+}
+impl PipelineRegistry {
+    pub(crate) fn new(shaders: HashMap<Cow<'static, str>, Cow<'static, str>>, cache: Option<PipelineCache>) -> Self {
+        Self {
+            shaders: RwLock::new(shaders),
+            pipelines: RwLock::new(HashMap::new()),
+            cache,
+        }
+    }
+
+    pub(crate) fn shaders(&self) -> HashMap<Cow<'static, str>, Cow<'static, str>> {
+        self.shaders.read().unwrap().clone()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.pipelines.read().unwrap().len()
+    }
+
+    pub(crate) fn cache(&self) -> Option<&PipelineCache> {
+        self.cache.as_ref()
+    }
+
+    pub(crate) fn get(&self, builder: &PipelineBuilder, key: &PipelineKey) -> Result<SharedPipeline, RenderError> {
+        if let Some(pipeline) = self.pipelines.read().unwrap().get(key) {
+            return Ok(pipeline.clone())
+        }
+
+        let source = self.shaders.read().unwrap().get(&key.shader).cloned()
+            .ok_or_else(|| RenderError::resource("Render pipeline", format!("Shader `{}` is not registered", key.shader)))?
+        ;
+        let pipeline = Arc::new(builder.build(self.cache_id(), key, &source)?);
+
+        // Another thread may have built the same pipeline meanwhile, the first one is kept
+        Ok(self.pipelines.write().unwrap().entry(key.clone()).or_insert(pipeline).clone())
+    }
+
+    /// Replaces the source of `name`. Every pipeline using it is rebuilt first,
+    /// so that nothing is replaced unless all of them succeed.
+    pub(crate) fn register(&self, builder: &PipelineBuilder, name: Cow<'static, str>, source: Cow<'static, str>) -> Result<(), RenderError> {
+        let keys = self.pipelines.read().unwrap().keys()
+            .filter(|key| key.shader == name)
+            .cloned()
+            .collect::<Vec<_>>()
+        ;
+
+        let rebuilt = match keys.is_empty() {
+            // Nothing to rebuild, but the source is still checked
+            true => { builder.validate(&name, &source)?; vec![] }
+            false => keys.into_iter()
+                .map(|key| builder.build(self.cache_id(), &key, &source).map(|pipeline| (key, Arc::new(pipeline))))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        self.pipelines.write().unwrap().extend(rebuilt);
+        self.shaders.write().unwrap().insert(name, source);
+        Ok(())
+    }
+
+    pub(crate) fn cache_id(&self) -> Option<PipelineCacheId> {
+        self.cache.as_ref().map(|cache| cache.cache.id)
+    }
+}
+
+/// What every pipeline of a context shares.
+pub(crate) struct PipelineBuilder<'a> {
+    pub(crate) instance: &'a WgpuInstance,
+    pub(crate) device_id: DeviceId,
+    pub(crate) layout_id: PipelineLayoutId,
+    pub(crate) options: &'a RenderOptions,
+}
+impl PipelineBuilder<'_> {
+    fn create_shader(&self, label: &str, source: &str) -> Result<AutoDropId<wgpu::wgc::id::ShaderModuleId>, RenderError> {
+        let source = wgpu::wgc::pipeline::ShaderModuleSource::Wgsl(Cow::Borrowed(source));
+        let desc = wgpu::wgc::pipeline::ShaderModuleDescriptor {
+            label: Some(Cow::Borrowed(label)),
+            runtime_checks: wgpu::wgt::ShaderRuntimeChecks::checked(),
+        };
+        let (shader_id, err) = self.instance.0.device_create_shader_module(self.device_id, &desc, source, None);
+        let shader = self.instance.as_auto_drop(shader_id);
+        if let Some(err) = err { return Err(shader_error(label.to_string(), err)) }
+
+        Ok(shader)
+    }
+
+    fn validate(&self, label: &str, source: &str) -> Result<(), RenderError> {
+        self.create_shader(label, source).map(|_| ())
+    }
+
+    fn build(&self, cache: Option<PipelineCacheId>, key: &PipelineKey, source: &str) -> Result<AutoDropId<RenderPipelineId>, RenderError> {
+        let shader = self.create_shader(&key.shader, source)?;
+
+        let required_feature = match key.polygon_mode {
+            wgpu::wgt::PolygonMode::Fill => wgpu::wgt::Features::empty(),
+            wgpu::wgt::PolygonMode::Line => wgpu::wgt::Features::POLYGON_MODE_LINE,
+            wgpu::wgt::PolygonMode::Point => wgpu::wgt::Features::POLYGON_MODE_POINT,
+        };
+        // Pipelines are cached, so this is only reported once for each key
+        let polygon_mode = match self.instance.0.device_features(self.device_id).contains(required_feature) {
+            true => key.polygon_mode,
+            false => {
+                log::warn!("Polygon mode {:?} is not supported by the device, falling back to fill", key.polygon_mode);
+                wgpu::wgt::PolygonMode::Fill
+            }
+        };
+
+        let desc = wgpu::wgc::pipeline::RenderPipelineDescriptor {
+            label: Some(Cow::Borrowed("Render pipeline")),
+            layout: Some(self.layout_id),
+            vertex: wgpu::wgc::pipeline::VertexState {
+                stage: wgpu::wgc::pipeline::ProgrammableStageDescriptor {
+                    module: shader.id,
+                    entry_point: Some(Cow::Borrowed("vs_main")),
+                    constants: wgpu::naga::back::PipelineConstants::default(),
+                    zero_initialize_workgroup_memory: false,
+                },
+                buffers: Cow::Owned(vec![key.vertex_layout.desc()]),
+            },
+            fragment: Some(wgpu::wgc::pipeline::FragmentState {
+                stage: wgpu::wgc::pipeline::ProgrammableStageDescriptor {
+                    module: shader.id,
+                    entry_point: Some(Cow::Borrowed("fs_main")),
+                    constants: wgpu::naga::back::PipelineConstants::default(),
+                    zero_initialize_workgroup_memory: false,
+                },
+                targets: Cow::Owned(vec![
+                    Some(wgpu::wgt::ColorTargetState {
+                        format: key.format,
+                        blend: key.blend,
+                        write_mask: wgpu::wgt::ColorWrites::ALL,
+                    })
+                ]),
+            }),
+            primitive: wgpu::wgt::PrimitiveState {
+                topology: key.topology,
+                strip_index_format: None,
+                front_face: wgpu::wgt::FrontFace::Ccw,
+                cull_mode: key.cull_mode,
+                polygon_mode,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            multisample: wgpu::wgt::MultisampleState {
+                count: self.options.sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            // The render pass has a depth attachment either way, so the pipelines without depth test still declare it
+            depth_stencil: self.options.depth_format.map(|format| wgpu::wgt::DepthStencilState {
+                format,
+                depth_write_enabled: key.depth_compare.is_some() && key.is_opaque(),
+                depth_compare: key.depth_compare.unwrap_or(wgpu::wgt::CompareFunction::Always),
+                stencil: wgpu::wgt::StencilState::default(),
+                bias: wgpu::wgt::DepthBiasState::default(),
+            }),
+            multiview_mask: None,
+            cache,
+        };
+        let (pipeline_id, err) = self.instance.0.device_create_render_pipeline(self.device_id, &desc, None);
+        let pipeline = self.instance.as_auto_drop(pipeline_id);
+        if let Some(err) = err { return Err(RenderError::resource("Render pipeline", err)) }
+
+        Ok(pipeline)
+    }
+}
+
+/// Attaches the line and column reported by naga, if any.
+pub(crate) fn shader_error(label: String, err: wgpu::wgc::pipeline::CreateShaderModuleError) -> RenderError {
+    let location = match &err {
+        wgpu::wgc::pipeline::CreateShaderModuleError::Parsing(err) => err.inner.location(&err.source),
+        wgpu::wgc::pipeline::CreateShaderModuleError::Validation(err) => err.inner.location(&err.source),
+        _ => None,
+    };
+
+    RenderError::Shader {
+        label: label.into(),
+        location: location.map(|loc| (loc.line_number, loc.line_position)),
+        source: Box::new(err),
+    }
+}
+
+/// The driver's compiled pipelines, loaded from and saved to a file so that later runs start faster.
+pub(crate) struct PipelineCache {
+    cache: AutoDropId<PipelineCacheId>,
+    path: PathBuf,
+}
+impl PipelineCache {
+    /// Only adapters with a cache key (Vulkan for now) can share the data between runs.
+    pub(crate) fn locate(instance: &WgpuInstance, adapter_id: wgpu::wgc::id::AdapterId, dir: &Path) -> Option<PathBuf> {
+        let info = instance.0.adapter_get_info(adapter_id);
+        wgpu::util::pipeline_cache_key(&info).map(|key| dir.join(key))
+    }
+
+    pub(crate) fn load(instance: &WgpuInstance, device_id: DeviceId, path: PathBuf) -> Result<Self, RenderError> {
+        // A missing file is the first run, so only the other failures are reported
+        let data = match std::fs::read(&path) {
+            Ok(data) => Some(data),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => {
+                log::warn!("Failed to read the pipeline cache {}, starting empty (cause: {err})", path.display());
+                None
+            }
+        };
+
+        let desc = wgpu::wgc::pipeline::PipelineCacheDescriptor {
+            label: Some(Cow::Borrowed("Pipeline cache")),
+            data: data.map(Cow::Owned),
+            // Data from another driver version is discarded instead of failing
+            fallback: true,
+        };
+        // SAFETY: The data was written by `save` for the adapter named by the file, and wgpu validates its header
+        let (cache_id, err) = unsafe { instance.0.device_create_pipeline_cache(device_id, &desc, None) };
+        let cache = instance.as_auto_drop(cache_id);
+        if let Some(err) = err { return Err(RenderError::resource("Pipeline cache", err)) }
+
+        Ok(Self { cache, path })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes through a temporary file, so that a crash never leaves a truncated cache behind.
+    pub(crate) fn save(&self, instance: &WgpuInstance) -> std::io::Result<()> {
+        let Some(data) = instance.0.pipeline_cache_get_data(self.cache.id) else { return Ok(()) };
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let temp_path = self.path.with_extension("tmp");
+        std::fs::write(&temp_path, data)?;
+        std::fs::rename(&temp_path, &self.path)
+    }
+}
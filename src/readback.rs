@@ -0,0 +1,126 @@
+use std::borrow::Cow;
+
+use wgpu::wgc::id::{BufferId, CommandEncoderId, TextureId};
+use wgpu::wgt::TextureFormat;
+
+use crate::{error::RenderError, runtime, wgpu_resource::AutoDropId};
+
+/// A mappable buffer receiving a copy of a color texture.
+pub(crate) struct Readback {
+    buffer: AutoDropId<BufferId>,
+    size: (u32, u32),
+    format: TextureFormat,
+    padded_bytes_per_row: u32,
+}
+impl Readback {
+    pub(crate) fn new(context: &runtime::RenderContext, (width, height): (u32, u32), format: TextureFormat) -> Result<Self, RenderError> {
+        if ! is_supported_format(format) { return Err(RenderError::readback(format!("Readback is not supported for the texture format: {format:?}"))) }
+
+        // Each row of the copy destination must be aligned to `COPY_BYTES_PER_ROW_ALIGNMENT`
+        let padded_bytes_per_row = (bytes_per_texel(format) * width).next_multiple_of(wgpu::wgt::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let desc = wgpu::wgt::BufferDescriptor {
+            label: Some(Cow::Borrowed("Readback buffer")),
+            size: (padded_bytes_per_row * height) as u64,
+            mapped_at_creation: false,
+            usage: wgpu::wgt::BufferUsages::MAP_READ | wgpu::wgt::BufferUsages::COPY_DST,
+        };
+        let (buffer_id, err) = context.instance.0.device_create_buffer(context.device.id, &desc, None);
+        let buffer = context.instance.as_auto_drop(buffer_id);
+        if let Some(err) = err { return Err(RenderError::resource("Readback buffer", err)) }
+
+        Ok(Self { buffer, size: (width, height), format, padded_bytes_per_row })
+    }
+
+    /// Records the copy of `texture` into the readback buffer.
+    pub(crate) fn encode_copy(&self, context: &runtime::RenderContext, encoder_id: CommandEncoderId, texture_id: TextureId) -> Result<(), RenderError> {
+        let (width, height) = self.size;
+
+        let source = wgpu::wgt::TexelCopyTextureInfo {
+            texture: texture_id,
+            mip_level: 0,
+            origin: wgpu::wgt::Origin3d::ZERO,
+            aspect: wgpu::wgt::TextureAspect::All,
+        };
+        let dest = wgpu::wgt::TexelCopyBufferInfo {
+            buffer: self.buffer.id,
+            layout: wgpu::wgt::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(self.padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        };
+        let size = wgpu::wgt::Extent3d { width, height, depth_or_array_layers: 1 };
+        context.instance.0.command_encoder_copy_texture_to_buffer(encoder_id, &source, &dest, &size)
+            .map_err(|err| RenderError::encode("Readback copy", err))?;
+
+        Ok(())
+    }
+
+    /// Waits for the submitted copy and converts the texels into an RGBA image.
+    /// The bytes are kept as they are stored, so an sRGB texture yields sRGB encoded pixels as expected by PNG.
+    /// `Rgba16Float` texels hold linear values, which are clamped to the displayable range and sRGB encoded.
+    pub(crate) fn into_image(self, context: &runtime::RenderContext) -> Result<image::RgbaImage, RenderError> {
+        let (width, height) = self.size;
+        let readback_size = (self.padded_bytes_per_row * height) as u64;
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let op = wgpu::wgc::resource::BufferMapOperation {
+            host: wgpu::wgc::device::HostMap::Read,
+            callback: Some(Box::new(move |result| { let _ = sender.send(result); })),
+        };
+        context.instance.0.buffer_map_async(self.buffer.id, 0, Some(readback_size), op).map_err(RenderError::readback)?;
+        context.instance.0.device_poll(context.device.id, wgpu::wgt::PollType::wait_indefinitely()).map_err(RenderError::readback)?;
+        receiver.recv().map_err(RenderError::readback)?.map_err(RenderError::readback)?;
+
+        let (ptr, _) = context.instance.0.buffer_get_mapped_range(self.buffer.id, 0, Some(readback_size)).map_err(RenderError::readback)?;
+        let mapped = unsafe { std::slice::from_raw_parts(ptr.as_ptr(), readback_size as usize) };
+        let mut pixels = mapped
+            .chunks_exact(self.padded_bytes_per_row as usize)
+            .flat_map(|row| &row[..(bytes_per_texel(self.format) * width) as usize])
+            .copied()
+            .collect::<Vec<_>>()
+        ;
+        context.instance.0.buffer_unmap(self.buffer.id).map_err(RenderError::readback)?;
+
+        if matches!(self.format, TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb) {
+            for texel in pixels.chunks_exact_mut(4) {
+                texel.swap(0, 2);
+            }
+        }
+        if self.format == TextureFormat::Rgba16Float {
+            pixels = pixels
+                .chunks_exact(2)
+                .enumerate()
+                .map(|(channel, bytes)| {
+                    let value = half::f16::from_le_bytes([bytes[0], bytes[1]]).to_f32();
+                    // Alpha is not color, so it is stored without the sRGB transfer function
+                    if channel % 4 == 3 { unorm8(value) } else { unorm8(linear_to_srgb(value)) }
+                })
+                .collect()
+            ;
+        }
+
+        image::RgbaImage::from_raw(width, height, pixels).ok_or_else(|| RenderError::readback("Readback buffer is too small"))
+    }
+}
+
+pub(crate) fn is_supported_format(format: TextureFormat) -> bool {
+    matches!(format,
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb |
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb |
+        TextureFormat::Rgba16Float
+    )
+}
+
+fn bytes_per_texel(format: TextureFormat) -> u32 {
+    format.block_copy_size(None).expect("Readback formats are single-aspect")
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 { 12.92 * value } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 }
+}
+
+fn unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
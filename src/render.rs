@@ -1,234 +1,765 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
-use wgpu::BufferSize;
-use wgpu::wgc::id::{BindGroupId, BufferId};
+use wgpu::wgc::id::{BindGroupId, CommandBufferId, TextureId, TextureViewId};
 use wgpu::{wgc::id::SurfaceId, wgt::SurfaceConfiguration};
-use wgpu::wgt::TextureFormat;
-use crate::{runtime, wgpu_resource::AutoDropId};
+use wgpu::wgt::{PresentMode, TextureFormat};
+use crate::{camera::{Camera, CameraBinding}, error::RenderError, mesh::{Mesh, MeshData}, pipeline::PipelineKey, readback::{self, Readback}, runtime, scene::Scene, surface::{self, Acquired, PendingFrame, SurfaceLayer}, texture::{SamplerOptions, Texture}, wgpu_resource::AutoDropId};
 
-const IMAGE: &'static [u8] = include_bytes!("../assets/img/happy-tree.png");
+/// Where the renderer draws its frames into.
+enum RenderTarget {
+    /// The swapchain of a window surface.
+    Surface(AutoDropId<SurfaceId>),
+    /// A texture owned by the renderer, read back with `WgpuRenderer::read_pixels`.
+    Offscreen(AutoDropId<TextureId>),
+}
 
 pub struct WgpuRenderer {
     context: Arc<runtime::RenderContext>,
-    surface: AutoDropId<SurfaceId>,
+    target: RenderTarget,
     config: SurfaceConfiguration<Vec<TextureFormat>>,
-    vertex_buffer: AutoDropId<BufferId>,
-    index_buffer: AutoDropId<BufferId>,
+    /// Present modes in order of preference, selected again when the surface capabilities may have changed.
+    present_modes: Vec<PresentMode>,
+    /// Present when the context is configured with a depth format, sized as the target.
+    depth_view: Option<AutoDropId<TextureViewId>>,
+    /// Present when the context is configured with multisampling, resolved into the target every frame.
+    msaa_view: Option<AutoDropId<TextureViewId>>,
+    /// The pipeline of the meshes that do not choose their own.
+    pipeline_key: PipelineKey,
+    /// Draws every mesh as wireframe, whatever its pipeline.
+    wireframe: bool,
+    meshes: Vec<Mesh>,
+    /// Textures drawn in place of the image, shared by the meshes with the same base color.
+    materials: Vec<Material>,
+    /// Index in `materials` for each mesh, `None` to draw the image.
+    mesh_materials: Vec<Option<usize>>,
+    /// How the base colors of the materials are sampled.
+    material_sampler: SamplerOptions,
     image_bind_group: AutoDropId<BindGroupId>,
+    camera: Camera,
+    camera_binding: CameraBinding,
+    dirty_camera: bool,
+    pending_capture: Option<PathBuf>,
+    minimized: bool,
 }
 impl WgpuRenderer {
-    pub fn new(context: Arc<runtime::RenderContext>, surface_id: SurfaceId, (width, height): (u32, u32)) -> Result<Self, anyhow::Error> {
-        let mut config = context.config.clone();
-        config.width = width;
-        config.height = height;
-
-        let vertex_size = (crate::VERTICES.len() * size_of::<crate::Vertex>()) as u64;
-
-        let desc = wgpu::wgt::BufferDescriptor {
-            label: Some("Vertex buffer").map(Cow::Borrowed),
-            size: vertex_size,
-            mapped_at_creation: false, // For staging copy
-            usage: wgpu::wgt::BufferUsages::VERTEX | wgpu::wgt::BufferUsages::COPY_DST,
-        };
-        let (buffer_id, err) = context.instance.0.device_create_buffer(context.device.id, &desc, None);
-        let vbuffer = context.instance.as_auto_drop(buffer_id);
-        if let Some(err) = err { anyhow::bail!("{err}") }
-
-        let index_size = (crate::INDICES.len() * size_of::<u32>()) as u64;
-        let desc = wgpu::wgt::BufferDescriptor {
-            label: Some("Index buffer").map(Cow::Borrowed),
-            size: index_size,
-            mapped_at_creation: false, // For staging copy
-            usage: wgpu::wgt::BufferUsages::INDEX | wgpu::wgt::BufferUsages::COPY_DST,
-        };
-        let (buffer_id, err) = context.instance.0.device_create_buffer(context.device.id, &desc, None);
-        let ibuffer = context.instance.as_auto_drop(buffer_id);
-        if let Some(err) = err { anyhow::bail!("{err}") }
-
-        let image_bind_group = {
-            let image = image::load_from_memory(IMAGE)?.to_rgba8();
-            let dims = image.dimensions();
-            let size = wgpu::wgt::Extent3d { width: dims.0, height: dims.1, depth_or_array_layers: 1 };
-            let desc = wgpu::wgt::TextureDescriptor {
-                label: Some("Diffuse texture").map(Cow::Borrowed),
-                size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::wgt::TextureDimension::D2,
-                format: wgpu::wgt::TextureFormat::Rgba8UnormSrgb,
-                usage: wgpu::wgt::TextureUsages::TEXTURE_BINDING | wgpu::wgt::TextureUsages::COPY_DST,
-                view_formats: vec![],
-            };
-            let (texture_id, err) = context.instance.0.device_create_texture(context.device.id, &desc, None);
-            let texture = context.instance.as_auto_drop(texture_id);
-            if let Some(err) = err { anyhow::bail!("{err}") }
-
-            let dest = wgpu::wgt::TexelCopyTextureInfo {
-                texture: texture.id,
-                mip_level: 0,
-                origin: wgpu::wgt::Origin3d::ZERO,
-                aspect: wgpu::wgt::TextureAspect::All,
-            };
-            let layout = wgpu::wgt::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * dims.0),
-                rows_per_image: Some(dims.1),
-            };
-            context.instance.0.queue_write_texture(context.queue.id, &dest, &image, &layout, &size)?;
-
-            let desc = wgpu::wgc::resource::TextureViewDescriptor::default();
-            let (view_id, err) = context.instance.0.texture_create_view(texture.id, &desc, None);
-            let view = context.instance.as_auto_drop(view_id);
-            if let Some(err) = err { anyhow::bail!("{err}") }
-
-            let desc: wgpu::wgt::SamplerDescriptor<Cow<'_, &str>> = wgpu::wgt::SamplerDescriptor::default();
-            let desc = wgpu::wgc::resource::SamplerDescriptor {
-                label: Some("Diffuse texture sampler").map(Cow::Borrowed),
-                address_modes: [
-                    wgpu::wgt::AddressMode::ClampToEdge, // u
-                    wgpu::wgt::AddressMode::ClampToEdge, // v
-                    wgpu::wgt::AddressMode::ClampToEdge, // w
-                ],
-                mag_filter: wgpu::wgt::FilterMode::Linear,
-                min_filter: wgpu::wgt::FilterMode::Nearest,
-                mipmap_filter: wgpu::wgt::MipmapFilterMode::Nearest,
-                lod_min_clamp: desc.lod_min_clamp,
-                lod_max_clamp: desc.lod_max_clamp,
-                compare: desc.compare,
-                anisotropy_clamp: desc.anisotropy_clamp,
-                border_color: desc.border_color,
-            };
-            let (sampler_id, err) = context.instance.0.device_create_sampler(context.device.id, &desc, None);
-            let sampler = context.instance.as_auto_drop(sampler_id);
-            if let Some(err) = err { anyhow::bail!("{err}") }
-
-            let desc = wgpu::wgc::binding_model::BindGroupDescriptor {
-                label: Some("Diffuse texture bind group").map(Cow::Borrowed),
-                layout: context.bing_group_layout.id,
-                entries: Cow::Borrowed(&[
-                    wgpu::wgc::binding_model::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::wgc::binding_model::BindingResource::TextureView(view.id),
-                    },
-                    wgpu::wgc::binding_model::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::wgc::binding_model::BindingResource::Sampler(sampler.id),
-                    },
-                ]),
-            };
-            let (group_id, err) = context.instance.0.device_create_bind_group(context.device.id, &desc, None);
-            let bind_group = context.instance.as_auto_drop(group_id);
-            if let Some(err) = err { anyhow::bail!("{err}") }
-            bind_group
-        };
+    pub fn new(context: Arc<runtime::RenderContext>, surface_id: SurfaceId, size: (u32, u32), texture: &Texture) -> Result<Self, RenderError> {
+        let target = RenderTarget::Surface(context.instance.as_auto_drop(surface_id));
+        Self::with_target(context, target, size, texture)
+    }
+
+    /// Creates a renderer drawing into an owned texture instead of a window surface.
+    pub fn new_offscreen(context: Arc<runtime::RenderContext>, size: (u32, u32), texture: &Texture) -> Result<Self, RenderError> {
+        let target_texture = create_offscreen_texture(&context, size)?;
+        Self::with_target(context, RenderTarget::Offscreen(target_texture), size, texture)
+    }
+
+    fn with_target(context: Arc<runtime::RenderContext>, target: RenderTarget, (width, height): (u32, u32), texture: &Texture) -> Result<Self, RenderError> {
+        let present_modes = context.options.present_modes.clone();
+        let config = target_config(&context, &target, &present_modes, (width, height))?;
+
+        let depth_view = create_depth_view(&context, (width, height))?;
+        let msaa_view = create_msaa_view(&context, (width, height), runtime::color_format(&config))?;
+        let mesh = Mesh::new(&context, MeshData::pentagon())?;
+
+        let image_bind_group = create_image_bind_group(&context, texture)?;
+        let pipeline_key = PipelineKey::new(runtime::color_format(&config));
+
+        let mut camera = Camera::new();
+        camera.set_aspect((width, height));
+        let camera_binding = CameraBinding::new(&context, &camera)?;
 
         Ok(Self {
-            surface: context.instance.as_auto_drop(surface_id),
+            target,
             config,
+            present_modes,
             context,
-            vertex_buffer: vbuffer,
-            index_buffer: ibuffer,
+            depth_view,
+            msaa_view,
+            pipeline_key,
+            wireframe: false,
+            meshes: vec![mesh],
+            materials: vec![],
+            mesh_materials: vec![None],
+            material_sampler: SamplerOptions::default(),
             image_bind_group,
+            camera,
+            camera_binding,
+            dirty_camera: false,
+            pending_capture: None,
+            minimized: false,
         })
     }
 
+    pub fn context(&self) -> &runtime::RenderContext {
+        &self.context
+    }
+
+    pub fn surface_id(&self) -> Option<SurfaceId> {
+        match &self.target {
+            RenderTarget::Surface(surface) => Some(surface.id),
+            RenderTarget::Offscreen(_) => None,
+        }
+    }
+
+    /// Moves the renderer onto a recreated context after the device has been lost.
+    /// `texture` must have been uploaded through the new context.
+    pub fn recreate(&mut self, context: Arc<runtime::RenderContext>, texture: &Texture) -> Result<(), RenderError> {
+        let meshes = self.meshes.iter().map(|mesh| mesh.recreate(&context)).collect::<Result<Vec<_>, _>>()?;
+        let materials = self.materials.iter()
+            .map(|material| Material::new(&context, material.image.clone(), &self.material_sampler))
+            .collect::<Result<Vec<_>, _>>()?
+        ;
+        let image_bind_group = create_image_bind_group(&context, texture)?;
+        let camera_binding = CameraBinding::new(&context, &self.camera)?;
+
+        // The new adapter may offer other formats and modes
+        let mut config = target_config(&context, &self.target, &self.present_modes, (self.config.width, self.config.height))?;
+        config.desired_maximum_frame_latency = self.config.desired_maximum_frame_latency;
+
+        match &mut self.target {
+            RenderTarget::Surface(surface) => configure_surface(&context, surface.id, &config)?,
+            RenderTarget::Offscreen(target_texture) => *target_texture = create_offscreen_texture(&context, (config.width, config.height))?,
+        }
+        self.depth_view = create_depth_view(&context, (config.width, config.height))?;
+        self.msaa_view = create_msaa_view(&context, (config.width, config.height), runtime::color_format(&config))?;
+        self.pipeline_key.format = runtime::color_format(&config);
+
+        self.meshes = meshes;
+        self.materials = materials;
+        self.image_bind_group = image_bind_group;
+        self.camera_binding = camera_binding;
+        self.dirty_camera = false;
+        self.config = config;
+        self.context = context;
+        Ok(())
+    }
+
+    /// The format frames are drawn in, negotiated with the surface. Pipelines are built for it.
+    pub fn color_format(&self) -> TextureFormat {
+        runtime::color_format(&self.config)
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        self.config.present_mode
+    }
+
+    /// The present modes of the surface. Offscreen renderers are never throttled, and report `Fifo` alone.
+    pub fn supported_present_modes(&self) -> Result<Vec<PresentMode>, RenderError> {
+        supported_present_modes(&self.context, &self.target)
+    }
+
+    /// Switches to the first of `preferences` supported by the surface (see `RenderOptions::present_modes`),
+    /// and returns the selected mode.
+    pub fn set_present_modes(&mut self, preferences: &[PresentMode]) -> Result<PresentMode, RenderError> {
+        self.config.present_mode = runtime::select_present_mode(preferences, &self.supported_present_modes()?);
+        self.present_modes = preferences.to_vec();
+        self.reconfigure()?;
+        Ok(self.config.present_mode)
+    }
+
+    pub fn frame_latency(&self) -> u32 {
+        self.config.desired_maximum_frame_latency
+    }
+
+    /// Sets the number of frames queued ahead of the display. The surface may clamp it.
+    pub fn set_frame_latency(&mut self, latency: u32) -> Result<(), RenderError> {
+        self.config.desired_maximum_frame_latency = latency;
+        self.reconfigure()
+    }
+
+    /// Applies the configuration to the surface, unless it waits for a size.
+    fn reconfigure(&self) -> Result<(), RenderError> {
+        let has_size = ! self.minimized && self.config.width > 0 && self.config.height > 0;
+        match &self.target {
+            RenderTarget::Surface(surface) if has_size => configure_surface(&self.context, surface.id, &self.config),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn pipeline_key(&self) -> &PipelineKey {
+        &self.pipeline_key
+    }
+
+    /// Draws the meshes with the pipeline of `key`, built on first use and shared with other renderers.
+    pub fn set_pipeline_key(&mut self, key: PipelineKey) {
+        self.pipeline_key = key;
+    }
+
+    pub fn wireframe(&self) -> bool {
+        self.wireframe
+    }
+
+    /// Shows the edges of every triangle, falling back to filled triangles when the device cannot draw lines.
+    pub fn set_wireframe(&mut self, wireframe: bool) {
+        self.wireframe = wireframe;
+    }
+
+    /// The meshes drawn every frame, in order. A renderer starts with the pentagon.
+    pub fn meshes(&self) -> &[Mesh] {
+        &self.meshes
+    }
+
+    /// Modifications of the meshes are uploaded before the next frame is drawn.
+    pub fn meshes_mut(&mut self) -> &mut [Mesh] {
+        &mut self.meshes
+    }
+
+    /// Uploads `data` and draws it on top of the current meshes. Returns the index in `meshes`.
+    pub fn add_mesh(&mut self, data: MeshData) -> Result<usize, RenderError> {
+        self.meshes.push(Mesh::new(&self.context, data)?);
+        self.mesh_materials.push(None);
+        Ok(self.meshes.len() - 1)
+    }
+
+    /// Replaces every mesh with `meshes`, e.g. to load another model.
+    /// They are drawn with the image of the renderer.
+    pub fn set_meshes(&mut self, meshes: Vec<Mesh>) {
+        self.materials = vec![];
+        self.mesh_materials = meshes.iter().map(|_| None).collect();
+        self.meshes = meshes;
+    }
+
+    /// Replaces every mesh with the ones of `scene`, each drawn with its own base color.
+    /// Blended or double-sided materials get their own pipeline, derived from the one of the renderer.
+    pub fn set_scene(&mut self, scene: &Scene) -> Result<(), RenderError> {
+        let mut meshes = Vec::with_capacity(scene.meshes.len());
+        let mut materials = Vec::new();
+        let mut material_indices = HashMap::new();
+        let mut mesh_materials = Vec::with_capacity(scene.meshes.len());
+
+        for item in &scene.meshes {
+            let mut mesh = Mesh::new(&self.context, item.data.clone())?;
+            if item.alpha_blend || item.double_sided {
+                let mut key = self.pipeline_key.clone();
+                if item.alpha_blend { key = key.alpha_blended() }
+                if item.double_sided { key = key.double_sided() }
+                mesh.set_pipeline(Some(key));
+            }
+            meshes.push(mesh);
+            // Primitives of a material share its image, which is uploaded once
+            let index = match material_indices.get(&Arc::as_ptr(&item.base_color)) {
+                Some(&index) => index,
+                None => {
+                    materials.push(Material::new(&self.context, item.base_color.clone(), &self.material_sampler)?);
+                    material_indices.insert(Arc::as_ptr(&item.base_color), materials.len() - 1);
+                    materials.len() - 1
+                }
+            };
+            mesh_materials.push(Some(index));
+        }
+
+        self.meshes = meshes;
+        self.materials = materials;
+        self.mesh_materials = mesh_materials;
+        Ok(())
+    }
+
+    /// Number of base color textures uploaded for the scene, one for each distinct image.
+    pub fn material_count(&self) -> usize {
+        self.materials.len()
+    }
+
+    pub fn material_sampler(&self) -> &SamplerOptions {
+        &self.material_sampler
+    }
+
+    /// Samples the base colors of the scene with `options`, while the image keeps the sampler of its texture.
+    pub fn set_material_sampler(&mut self, options: SamplerOptions) -> Result<(), RenderError> {
+        for material in &mut self.materials {
+            material.set_sampler(&self.context, options.clone())?;
+        }
+        self.material_sampler = options;
+        Ok(())
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// Modifications of the camera are uploaded before the next frame is drawn.
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        self.dirty_camera = true;
+        &mut self.camera
+    }
+
+    /// Replaces the image drawn on the pentagon.
+    pub fn set_texture(&mut self, texture: &Texture) -> Result<(), RenderError> {
+        self.image_bind_group = create_image_bind_group(&self.context, texture)?;
+        Ok(())
+    }
+
     pub fn request_resize(&mut self, (width, height): (u32, u32)) {
-        if (width > 0) && (height > 0) {
+        // A minimized window reports zero size, which cannot be configured
+        self.minimized = (width == 0) || (height == 0);
+
+        if ! self.minimized {
             self.config.width = width;
             self.config.height = height;
-            let _ = self.context.instance.0.surface_configure(self.surface.id, self.context.device.id, &self.config);
+            self.camera.set_aspect((width, height));
+            self.dirty_camera = true;
+            match &mut self.target {
+                RenderTarget::Surface(surface) => {
+                    if let Err(err) = configure_surface(&self.context, surface.id, &self.config) {
+                        log::error!("Failed to resize the surface (cause: {err})");
+                    }
+                }
+                RenderTarget::Offscreen(texture) => {
+                    match create_offscreen_texture(&self.context, (width, height)) {
+                        Ok(new_texture) => *texture = new_texture,
+                        Err(err) => log::error!("Failed to resize offscreen texture (cause: {err})"),
+                    }
+                }
+            }
+            match create_depth_view(&self.context, (width, height)) {
+                Ok(depth_view) => self.depth_view = depth_view,
+                Err(err) => log::error!("Failed to resize the depth texture (cause: {err})"),
+            }
+            match create_msaa_view(&self.context, (width, height), runtime::color_format(&self.config)) {
+                Ok(msaa_view) => self.msaa_view = msaa_view,
+                Err(err) => log::error!("Failed to resize the multisampled texture (cause: {err})"),
+            }
         }
     }
 
     #[track_caller]
-    pub fn render(&mut self) -> Result<(), anyhow::Error> {
-        // copy vertex data
-        let source = bytemuck::cast_slice(crate::VERTICES);
-        let vertex_size = BufferSize::new((crate::VERTICES.len() * size_of::<crate::Vertex>()) as u64).unwrap();
-
-        let (vertex_staging_id, vertex_staging_offset) = self.context.instance.0.queue_create_staging_buffer(
-            self.context.queue.id,
-            vertex_size,
-            None
-        )?;
-
-        let slice = unsafe { std::slice::from_raw_parts_mut(vertex_staging_offset.as_ptr(), source.len()) };
-        slice.copy_from_slice(source);
-        self.context.instance.0.queue_write_staging_buffer(
-            self.context.queue.id,
-            self.vertex_buffer.id,
-            0, // dst offset
-            vertex_staging_id
-        )?;
-
-        // copy index data
-        let source = bytemuck::cast_slice(crate::INDICES);
-        let index_len = crate::INDICES.len() as u32;
-        let index_size = BufferSize::new((crate::INDICES.len() * size_of::<u32>()) as u64).unwrap();
-
-        let (index_staging_id, index_staging_offset) = self.context.instance.0.queue_create_staging_buffer(
-            self.context.queue.id,
-            index_size,
-            None
-        )?;
-
-        let slice = unsafe { std::slice::from_raw_parts_mut(index_staging_offset.as_ptr(), source.len()) };
-        slice.copy_from_slice(source);
-        self.context.instance.0.queue_write_staging_buffer(
-            self.context.queue.id,
-            self.index_buffer.id,
-            0, // dst offset
-            index_staging_id
-        )?;
-
-        let desc = wgpu::wgt::CommandEncoderDescriptor { label: Some("Begin encode").map(Cow::Borrowed) };
+    pub fn render(&mut self) -> Result<(), RenderError> {
+        if self.context.is_lost() { return Err(RenderError::DeviceLost) }
+
+        // Any failure on a lost device is reported as such, so that callers can wait for the recreation
+        self.draw_frame().map_err(|err| match self.context.is_lost() {
+            true => RenderError::DeviceLost,
+            false => err,
+        })
+    }
+
+    fn draw_frame(&mut self) -> Result<(), RenderError> {
+        if self.minimized { return Ok(()) }
+
+        let mut swap_chain = match &self.target {
+            RenderTarget::Surface(surface) => Some(SwapChain { context: self.context.clone(), surface_id: surface.id, config: self.config.clone() }),
+            RenderTarget::Offscreen(_) => None,
+        };
+        let (texture_id, suboptimal) = match (swap_chain.as_mut(), &self.target) {
+            (Some(swap_chain), _) => match surface::acquire_frame(swap_chain)? {
+                Acquired::Frame { frame, suboptimal } => (frame, suboptimal),
+                Acquired::Skip(status) => {
+                    log::debug!("Skipped the frame (status: {status:?})");
+                    return Ok(());
+                }
+            },
+            (None, RenderTarget::Offscreen(texture)) => (texture.id, false),
+            (None, RenderTarget::Surface(_)) => unreachable!("Surfaces are drawn through a swap chain"),
+        };
+        // Discarded by any early return below
+        let frame = swap_chain.as_mut().map(PendingFrame::new);
+
+        for mesh in &mut self.meshes {
+            mesh.flush(&self.context)?;
+        }
+        if self.dirty_camera {
+            self.camera_binding.write(&self.context, &self.camera)?;
+            self.dirty_camera = false;
+        }
+
+        // Surfaces that cannot be copied from are captured by drawing the frame once more into a texture of their own
+        let format = runtime::color_format(&self.config);
+        let capture_texture = match (&self.target, &self.pending_capture) {
+            (RenderTarget::Surface(_), Some(_)) if ! self.config.usage.contains(wgpu::wgt::TextureUsages::COPY_SRC) => {
+                match create_color_texture(&self.context, "Capture texture", (self.config.width, self.config.height), format) {
+                    Ok(texture) => Some(texture),
+                    Err(err) => {
+                        log::error!("Failed to capture the frame (cause: {err})");
+                        self.pending_capture = None;
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        // Looked up before the pass, which then holds on to them
+        let mut draws = self.meshes.iter().zip(&self.mesh_materials)
+            .filter(|(mesh, _)| mesh.index_count() > 0)
+            .map(|(mesh, material)| {
+                let key = mesh.pipeline().unwrap_or(&self.pipeline_key);
+                // Keys may be shared between windows, whose surface formats differ
+                let key = match (self.wireframe, key.format == format) {
+                    (false, true) => Cow::Borrowed(key),
+                    (wireframe, _) => {
+                        let mut key = key.clone();
+                        key.format = format;
+                        Cow::Owned(if wireframe { key.wireframe() } else { key })
+                    }
+                };
+                Ok((mesh, material, key.is_opaque(), self.context.pipeline(&key)?))
+            })
+            .collect::<Result<Vec<_>, RenderError>>()?
+        ;
+        // Blended meshes come last so that the opaque ones behind them are already drawn, each group keeping its order
+        draws.sort_by_key(|(_, _, opaque, _)| ! opaque);
+
+        let desc = wgpu::wgt::CommandEncoderDescriptor { label: Some(Cow::Borrowed("Begin encode")) };
         let (encoder_id, err) = self.context.instance.0.device_create_command_encoder(self.context.device.id, &desc, None);
         let encoder = self.context.instance.as_auto_drop(encoder_id);
-        if let Some(err) = err { anyhow::bail!("{err}") }
+        if let Some(err) = err { return Err(RenderError::resource("Begin encode", err)) }
 
-        let surface_texture = self.context.instance.0.surface_get_current_texture(self.surface.id, None)?;
-        let Some(texture_id) = surface_texture.texture else { anyhow::bail!("Surface is not configured (cause: {:?}", surface_texture.status) };
-        let desc = wgpu::wgc::resource::TextureViewDescriptor::default();
+        // A non-sRGB surface is drawn through an sRGB view when it offers one
+        let desc = wgpu::wgc::resource::TextureViewDescriptor {
+            format: Some(runtime::color_format(&self.config)),
+            ..Default::default()
+        };
         let (view_id, err) = self.context.instance.0.texture_create_view(texture_id, &desc, None);
         let view = self.context.instance.as_auto_drop(view_id);
-        if let Some(err) = err { anyhow::bail!("{err}") }
-
-        let desc = wgpu::wgc::command::RenderPassDescriptor {
-            label: Some("Render pass").map(Cow::Borrowed),
-            color_attachments: Cow::Borrowed(&[
-                Some(wgpu::wgc::command::RenderPassColorAttachment {
-                    view: view.id,
-                    depth_slice: None,
-                    resolve_target: None,
-                    load_op: wgpu::wgc::command::LoadOp::Clear(wgpu::wgt::Color { r: 0.1, g: 0.2, b: 0.3, a: 1.0 }),
-                    store_op: wgpu::wgc::command::StoreOp::Store,
-                })
-            ]),
-            depth_stencil_attachment: None,
-            timestamp_writes: None,
-            occlusion_query_set: None,
-            multiview_mask: None,
+        if let Some(err) = err { return Err(RenderError::resource("Render target view", err)) }
+
+        let capture_view = match &capture_texture {
+            Some(texture) => {
+                let desc = wgpu::wgc::resource::TextureViewDescriptor::default();
+                let (view_id, err) = self.context.instance.0.texture_create_view(texture.id, &desc, None);
+                let view = self.context.instance.as_auto_drop(view_id);
+                if let Some(err) = err { return Err(RenderError::resource("Capture texture view", err)) }
+                Some(view)
+            }
+            None => None,
         };
 
-        let (mut pass, err) = self.context.instance.0.command_encoder_begin_render_pass(encoder.id, &desc);
-        if let Some(err) = err { anyhow::bail!("{err}") }
-        self.context.instance.0.render_pass_set_pipeline(&mut pass, self.context.pipeline.id)?;
-        self.context.instance.0.render_pass_set_bind_group(&mut pass, 0, Some(self.image_bind_group.id), &[])?;
-        self.context.instance.0.render_pass_set_vertex_buffer(&mut pass, 0, self.vertex_buffer.id, 0, None)?; // offset <- vertex buffer offset, size <- vertex buffer size
-        self.context.instance.0.render_pass_set_index_buffer(&mut pass, self.index_buffer.id, wgpu::wgt::IndexFormat::Uint32, 0, None)?;
-        self.context.instance.0.render_pass_draw_indexed(&mut pass, index_len, 1, 0, 0, 0)?;
-        self.context.instance.0.render_pass_end(&mut pass)?;
+        let depth_attachment = self.depth_view.as_ref().map(|view| wgpu::wgc::command::RenderPassDepthStencilAttachment {
+            view: view.id,
+            depth: wgpu::wgc::command::PassChannel {
+                load_op: Some(wgpu::wgc::command::LoadOp::Clear(Some(1.0))),
+                store_op: Some(wgpu::wgc::command::StoreOp::Discard),
+                read_only: false,
+            },
+            stencil: match self.context.options.depth_format.is_some_and(|format| format.has_stencil_aspect()) {
+                true => wgpu::wgc::command::PassChannel {
+                    load_op: Some(wgpu::wgc::command::LoadOp::Clear(Some(0))),
+                    store_op: Some(wgpu::wgc::command::StoreOp::Discard),
+                    read_only: false,
+                },
+                false => wgpu::wgc::command::PassChannel { load_op: None, store_op: None, read_only: false },
+            },
+        });
+
+        for target_view in capture_view.iter().chain([&view]) {
+            let (color_view, resolve_target) = match &self.msaa_view {
+                Some(msaa_view) => (msaa_view.id, Some(target_view.id)),
+                None => (target_view.id, None),
+            };
+
+            let desc = wgpu::wgc::command::RenderPassDescriptor {
+                label: Some(Cow::Borrowed("Render pass")),
+                color_attachments: Cow::Borrowed(&[
+                    Some(wgpu::wgc::command::RenderPassColorAttachment {
+                        view: color_view,
+                        depth_slice: None,
+                        resolve_target,
+                        load_op: wgpu::wgc::command::LoadOp::Clear(wgpu::wgt::Color { r: 0.1, g: 0.2, b: 0.3, a: 1.0 }),
+                        // Only the resolved samples are needed after the pass
+                        store_op: match resolve_target {
+                            Some(_) => wgpu::wgc::command::StoreOp::Discard,
+                            None => wgpu::wgc::command::StoreOp::Store,
+                        },
+                    })
+                ]),
+                depth_stencil_attachment: depth_attachment.as_ref(),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            };
+
+            let pass_error = |err: wgpu::wgc::command::PassStateError| RenderError::encode("Render pass", err);
+            let (mut pass, err) = self.context.instance.0.command_encoder_begin_render_pass(encoder.id, &desc);
+            if let Some(err) = err { return Err(RenderError::encode("Render pass", err)) }
+            self.context.instance.0.render_pass_set_bind_group(&mut pass, 1, Some(self.camera_binding.bind_group.id), &[]).map_err(pass_error)?;
+            let mut current_pipeline = None;
+            for (mesh, material, _, pipeline) in &draws {
+                if current_pipeline != Some(pipeline.id) {
+                    self.context.instance.0.render_pass_set_pipeline(&mut pass, pipeline.id).map_err(pass_error)?;
+                    current_pipeline = Some(pipeline.id);
+                }
+                let bind_group = material.map_or(&self.image_bind_group, |index| &self.materials[index].bind_group);
+                self.context.instance.0.render_pass_set_bind_group(&mut pass, 0, Some(bind_group.id), &[]).map_err(pass_error)?;
+                self.context.instance.0.render_pass_set_vertex_buffer(&mut pass, 0, mesh.vertex_buffer_id(), 0, None).map_err(pass_error)?; // offset <- vertex buffer offset, size <- vertex buffer size
+                self.context.instance.0.render_pass_set_index_buffer(&mut pass, mesh.index_buffer_id(), mesh.index_format(), 0, None).map_err(pass_error)?;
+                self.context.instance.0.render_pass_draw_indexed(&mut pass, mesh.index_count(), 1, 0, 0, 0).map_err(pass_error)?;
+            }
+            self.context.instance.0.render_pass_end(&mut pass).map_err(|err| RenderError::encode("Render pass", err))?;
+        }
 
-        let desc = wgpu::wgt::CommandBufferDescriptor { label: Some("Finish encode").map(Cow::Borrowed) };
+        let desc = wgpu::wgt::CommandBufferDescriptor { label: Some(Cow::Borrowed("Finish encode")) };
         let (buffer_id, err) = self.context.instance.0.command_encoder_finish(encoder.id, &desc, None);
         let buffer = self.context.instance.as_auto_drop(buffer_id);
-        if let Some((msg, err)) = err { anyhow::bail!("{msg} (cause: {err})") }
+        if let Some((label, err)) = err { return Err(RenderError::encode(label, err)) }
 
-        match self.context.instance.0.queue_submit(self.context.queue.id, &[buffer.id]) {
-            Ok(_) => self.context.instance.0.surface_present(self.surface.id)?,
-            Err((index, err)) => anyhow::bail!("{err} @ {index}"),
+        // A failed capture must not cost the frame, so it is recorded apart
+        let (capture_source, capture_format) = match &capture_texture {
+            Some(texture) => (texture.id, format),
+            None => (texture_id, self.config.format),
         };
+        let capture = self.pending_capture.take().and_then(|path| match self.encode_capture(capture_source, capture_format) {
+            Ok((readback, buffer)) => Some((path, readback, buffer)),
+            Err(err) => {
+                log::error!("Failed to capture the frame (cause: {err})");
+                None
+            }
+        });
+        let buffers = match &capture {
+            Some((_, _, capture_buffer)) => vec![buffer.id, capture_buffer.id],
+            None => vec![buffer.id],
+        };
+
+        match self.context.instance.0.queue_submit(self.context.queue.id, &buffers) {
+            Ok(_) => {
+                if let Some(frame) = frame {
+                    let swap_chain = frame.present()?;
+                    if suboptimal {
+                        surface::reconfigure_suboptimal(swap_chain);
+                    }
+                }
+            }
+            Err((_, err)) => return Err(RenderError::submit(err)),
+        };
+
+        if let Some((path, readback, _)) = capture {
+            match readback.into_image(&self.context).map(|image| image.save(&path)) {
+                Ok(Ok(_)) => log::info!("Saved the frame to {}", path.display()),
+                Ok(Err(err)) => log::error!("Failed to save the frame (cause: {err})"),
+                Err(err) => log::error!("Failed to capture the frame (cause: {err})"),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Records the copy of the frame `texture_id` in a command buffer of its own.
+    fn encode_capture(&self, texture_id: TextureId, format: TextureFormat) -> Result<(Readback, AutoDropId<CommandBufferId>), RenderError> {
+        let readback = Readback::new(&self.context, (self.config.width, self.config.height), format)?;
+
+        let desc = wgpu::wgt::CommandEncoderDescriptor { label: Some(Cow::Borrowed("Begin capture")) };
+        let (encoder_id, err) = self.context.instance.0.device_create_command_encoder(self.context.device.id, &desc, None);
+        let encoder = self.context.instance.as_auto_drop(encoder_id);
+        if let Some(err) = err { return Err(RenderError::resource("Begin capture", err)) }
+
+        readback.encode_copy(&self.context, encoder.id, texture_id)?;
+
+        let desc = wgpu::wgt::CommandBufferDescriptor { label: Some(Cow::Borrowed("Finish capture")) };
+        let (buffer_id, err) = self.context.instance.0.command_encoder_finish(encoder.id, &desc, None);
+        let buffer = self.context.instance.as_auto_drop(buffer_id);
+        if let Some((label, err)) = err { return Err(RenderError::encode(label, err)) }
+
+        Ok((readback, buffer))
+    }
+
+    /// Copies the last rendered frame of an offscreen renderer back to CPU memory.
+    pub fn read_pixels(&self) -> Result<image::RgbaImage, RenderError> {
+        let RenderTarget::Offscreen(texture) = &self.target else { return Err(RenderError::readback("Only offscreen renderer can be read back")) };
+
+        let readback = Readback::new(&self.context, (self.config.width, self.config.height), self.config.format)?;
+
+        let desc = wgpu::wgt::CommandEncoderDescriptor { label: Some(Cow::Borrowed("Begin readback")) };
+        let (encoder_id, err) = self.context.instance.0.device_create_command_encoder(self.context.device.id, &desc, None);
+        let encoder = self.context.instance.as_auto_drop(encoder_id);
+        if let Some(err) = err { return Err(RenderError::resource("Begin readback", err)) }
+
+        readback.encode_copy(&self.context, encoder.id, texture.id)?;
+
+        let desc = wgpu::wgt::CommandBufferDescriptor { label: Some(Cow::Borrowed("Finish readback")) };
+        let (buffer_id, err) = self.context.instance.0.command_encoder_finish(encoder.id, &desc, None);
+        let buffer = self.context.instance.as_auto_drop(buffer_id);
+        if let Some((label, err)) = err { return Err(RenderError::encode(label, err)) }
+
+        if let Err((_, err)) = self.context.instance.0.queue_submit(self.context.queue.id, &[buffer.id]) {
+            return Err(RenderError::submit(err))
+        }
+
+        readback.into_image(&self.context)
+    }
+
+    /// Requests to save the next rendered frame to `path` as PNG.
+    pub fn request_capture(&mut self, path: impl Into<PathBuf>) -> Result<(), RenderError> {
+        if ! readback::is_supported_format(self.config.format) {
+            return Err(RenderError::readback(format!("Readback is not supported for the texture format: {:?}", self.config.format)))
+        }
+        self.pending_capture = Some(path.into());
+        Ok(())
+    }
+}
+
+/// An image uploaded for a single mesh. The image is kept to upload it again after the device is lost.
+struct Material {
+    image: Arc<image::RgbaImage>,
+    texture: Texture,
+    bind_group: AutoDropId<BindGroupId>,
+}
+impl Material {
+    fn new(context: &runtime::RenderContext, image: Arc<image::RgbaImage>, sampler: &SamplerOptions) -> Result<Self, RenderError> {
+        let mut texture = Texture::from_image(context, &image).map_err(|err| RenderError::resource("Base color texture", err))?;
+        if texture.sampler_options() != sampler {
+            texture.set_sampler(context, sampler.clone()).map_err(|err| RenderError::resource("Base color sampler", err))?;
+        }
+        let bind_group = create_image_bind_group(context, &texture)?;
+        Ok(Self { image, texture, bind_group })
+    }
+
+    fn set_sampler(&mut self, context: &runtime::RenderContext, options: SamplerOptions) -> Result<(), RenderError> {
+        self.texture.set_sampler(context, options).map_err(|err| RenderError::resource("Base color sampler", err))?;
+        self.bind_group = create_image_bind_group(context, &self.texture)?;
+        Ok(())
+    }
+}
+
+struct SwapChain {
+    context: Arc<runtime::RenderContext>,
+    surface_id: SurfaceId,
+    config: SurfaceConfiguration<Vec<TextureFormat>>,
+}
+
+impl SurfaceLayer for SwapChain {
+    type Frame = TextureId;
+
+    fn acquire(&mut self) -> Result<(Option<TextureId>, wgpu::wgt::SurfaceStatus), RenderError> {
+        let output = self.context.instance.0.surface_get_current_texture(self.surface_id, None).map_err(RenderError::surface)?;
+        Ok((output.texture, output.status))
+    }
+
+    fn configure(&mut self) -> Result<(), RenderError> {
+        configure_surface(&self.context, self.surface_id, &self.config)
+    }
 
+    fn present(&mut self) -> Result<(), RenderError> {
+        self.context.instance.0.surface_present(self.surface_id).map_err(RenderError::surface)?;
         Ok(())
     }
+
+    fn discard(&mut self) {
+        if let Err(err) = self.context.instance.0.surface_texture_discard(self.surface_id) {
+            log::warn!("Failed to discard the frame (cause: {err})");
+        }
+    }
+}
+
+/// Negotiates the configuration of a surface, while offscreen targets are drawn in the color format of the context.
+fn target_config(context: &runtime::RenderContext, target: &RenderTarget, present_modes: &[PresentMode], (width, height): (u32, u32)) -> Result<SurfaceConfiguration<Vec<TextureFormat>>, RenderError> {
+    let config = match target {
+        RenderTarget::Surface(surface) => context.surface_config(surface.id, present_modes)?,
+        RenderTarget::Offscreen(_) => SurfaceConfiguration {
+            format: runtime::color_format(&context.config),
+            present_mode: runtime::select_present_mode(present_modes, &[PresentMode::Fifo]),
+            view_formats: vec![],
+            ..context.config.clone()
+        },
+    };
+    Ok(SurfaceConfiguration { width, height, ..config })
+}
+
+fn supported_present_modes(context: &runtime::RenderContext, target: &RenderTarget) -> Result<Vec<PresentMode>, RenderError> {
+    match target {
+        RenderTarget::Surface(surface) => Ok(context.surface_capabilities(surface.id)?.present_modes),
+        RenderTarget::Offscreen(_) => Ok(vec![PresentMode::Fifo]),
+    }
+}
+
+fn configure_surface(context: &runtime::RenderContext, surface_id: SurfaceId, config: &SurfaceConfiguration<Vec<TextureFormat>>) -> Result<(), RenderError> {
+    match context.instance.0.surface_configure(surface_id, context.device.id, config) {
+        Some(err) => Err(RenderError::surface(err)),
+        None => Ok(()),
+    }
+}
+
+fn create_image_bind_group(context: &runtime::RenderContext, texture: &Texture) -> Result<AutoDropId<BindGroupId>, RenderError> {
+    let desc = wgpu::wgc::binding_model::BindGroupDescriptor {
+        label: Some(Cow::Borrowed("Diffuse texture bind group")),
+        layout: context.bing_group_layout.id,
+        entries: Cow::Borrowed(&[
+            wgpu::wgc::binding_model::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::wgc::binding_model::BindingResource::TextureView(texture.view.id),
+            },
+            wgpu::wgc::binding_model::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::wgc::binding_model::BindingResource::Sampler(texture.sampler.id),
+            },
+        ]),
+    };
+    let (group_id, err) = context.instance.0.device_create_bind_group(context.device.id, &desc, None);
+    let bind_group = context.instance.as_auto_drop(group_id);
+    if let Some(err) = err { return Err(RenderError::resource("Diffuse texture bind group", err)) }
+
+    Ok(bind_group)
+}
+
+fn create_offscreen_texture(context: &runtime::RenderContext, size: (u32, u32)) -> Result<AutoDropId<TextureId>, RenderError> {
+    create_color_texture(context, "Offscreen texture", size, runtime::color_format(&context.config))
+}
+
+/// A texture that can be drawn into and copied from.
+fn create_color_texture(context: &runtime::RenderContext, label: &'static str, (width, height): (u32, u32), format: TextureFormat) -> Result<AutoDropId<TextureId>, RenderError> {
+    let desc = wgpu::wgt::TextureDescriptor {
+        label: Some(Cow::Borrowed(label)),
+        size: wgpu::wgt::Extent3d { width, height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::wgt::TextureDimension::D2,
+        format,
+        usage: wgpu::wgt::TextureUsages::RENDER_ATTACHMENT | wgpu::wgt::TextureUsages::COPY_SRC,
+        view_formats: vec![],
+    };
+    let (texture_id, err) = context.instance.0.device_create_texture(context.device.id, &desc, None);
+    let texture = context.instance.as_auto_drop(texture_id);
+    if let Some(err) = err { return Err(RenderError::resource(label, err)) }
+
+    Ok(texture)
+}
+
+fn create_depth_view(context: &runtime::RenderContext, (width, height): (u32, u32)) -> Result<Option<AutoDropId<TextureViewId>>, RenderError> {
+    let Some(format) = context.options.depth_format else { return Ok(None) };
+
+    let desc = wgpu::wgt::TextureDescriptor {
+        label: Some(Cow::Borrowed("Depth texture")),
+        size: wgpu::wgt::Extent3d { width, height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: context.options.sample_count,
+        dimension: wgpu::wgt::TextureDimension::D2,
+        format,
+        usage: wgpu::wgt::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: vec![],
+    };
+    let (texture_id, err) = context.instance.0.device_create_texture(context.device.id, &desc, None);
+    let texture = context.instance.as_auto_drop(texture_id);
+    if let Some(err) = err { return Err(RenderError::resource("Depth texture", err)) }
+
+    // The view keeps the texture alive
+    let desc = wgpu::wgc::resource::TextureViewDescriptor::default();
+    let (view_id, err) = context.instance.0.texture_create_view(texture.id, &desc, None);
+    let view = context.instance.as_auto_drop(view_id);
+    if let Some(err) = err { return Err(RenderError::resource("Depth texture view", err)) }
+
+    Ok(Some(view))
+}
+
+fn create_msaa_view(context: &runtime::RenderContext, (width, height): (u32, u32), format: TextureFormat) -> Result<Option<AutoDropId<TextureViewId>>, RenderError> {
+    if context.options.sample_count <= 1 { return Ok(None) }
+
+    let desc = wgpu::wgt::TextureDescriptor {
+        label: Some(Cow::Borrowed("Multisampled texture")),
+        size: wgpu::wgt::Extent3d { width, height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: context.options.sample_count,
+        dimension: wgpu::wgt::TextureDimension::D2,
+        format,
+        usage: wgpu::wgt::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: vec![],
+    };
+    let (texture_id, err) = context.instance.0.device_create_texture(context.device.id, &desc, None);
+    let texture = context.instance.as_auto_drop(texture_id);
+    if let Some(err) = err { return Err(RenderError::resource("Multisampled texture", err)) }
+
+    let desc = wgpu::wgc::resource::TextureViewDescriptor::default();
+    let (view_id, err) = context.instance.0.texture_create_view(texture.id, &desc, None);
+    let view = context.instance.as_auto_drop(view_id);
+    if let Some(err) = err { return Err(RenderError::resource("Multisampled texture view", err)) }
+
+    Ok(Some(view))
 }
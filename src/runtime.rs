@@ -1,9 +1,14 @@
-use std::{borrow::Cow, sync::Arc};
+use std::{borrow::Cow, collections::HashMap, path::{Path, PathBuf}, sync::{Arc, atomic::{AtomicBool, Ordering}}};
 
-use crate::wgpu_resource::{AutoDropId, WgpuInstance};
+use crate::{error::RenderError, mipmap::MipmapGenerator, window::{WindowKey, WindowSpec}, pipeline::{self, PipelineBuilder, PipelineCache, PipelineKey, PipelineRegistry}, wgpu_resource::{AutoDropId, WgpuInstance}};
 
 pub enum UserEvent {
-    RequestNew,
+    RequestNew { key: WindowKey, spec: WindowSpec },
+    Close(WindowKey),
+    SetTitle(WindowKey, String),
+    Resize(WindowKey, (u32, u32)),
+    ReloadShader { path: PathBuf, source: String },
+    DeviceLost(String),
 }
 
 pub trait AsRawWindow {
@@ -21,58 +26,396 @@ impl RawWindowHandle {
     }
 }
 
+/// Settings fixed for the lifetime of a rendering context.
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    /// Format of the depth/stencil attachment of every renderer. `None` draws without depth testing.
+    pub depth_format: Option<wgpu::wgt::TextureFormat>,
+    /// Samples per pixel. Above 1, frames are drawn into a multisampled texture and resolved into the target.
+    /// Lowered to what the adapter supports when the context is created.
+    pub sample_count: u32,
+    /// Directory where compiled pipelines are kept between runs. Ignored when the adapter has no pipeline cache.
+    pub pipeline_cache_dir: Option<PathBuf>,
+    /// Present modes in order of preference. Each surface uses the first one it supports, or `Fifo` which all do.
+    /// `AutoVsync` and `AutoNoVsync` resolve to the best supported mode with and without vertical sync.
+    pub present_modes: Vec<wgpu::wgt::PresentMode>,
+    /// Frames queued ahead of the display. 1 lowers the latency, 2 and more keep the frame rate steadier.
+    pub desired_maximum_frame_latency: u32,
+    /// Prefers a `Rgba16Float` surface for HDR output, where the surface offers one.
+    /// Headless renderers then draw into a `Rgba16Float` target as well.
+    pub hdr: bool,
+    /// Composition of the windows with what is behind them. Falls back to `Auto` where a surface does not support it.
+    pub alpha_mode: wgpu::wgt::CompositeAlphaMode,
+}
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            depth_format: Some(wgpu::wgt::TextureFormat::Depth32Float),
+            sample_count: 1,
+            pipeline_cache_dir: None,
+            present_modes: vec![wgpu::wgt::PresentMode::AutoVsync],
+            desired_maximum_frame_latency: 2,
+            hdr: false,
+            alpha_mode: wgpu::wgt::CompositeAlphaMode::Auto,
+        }
+    }
+}
+
 pub struct RenderContext {
     pub(crate) instance: WgpuInstance,
+    adapter: AutoDropId<wgpu::wgc::id::AdapterId>,
     pub(crate) device:  AutoDropId<wgpu::wgc::id::DeviceId>,
     pub(crate) queue: AutoDropId<wgpu::wgc::id::QueueId>,
-    pub(crate) pipeline: AutoDropId<wgpu::wgc::id::RenderPipelineId>,
+    pub(crate) pipeline_layout: AutoDropId<wgpu::wgc::id::PipelineLayoutId>,
     pub(crate) bing_group_layout: AutoDropId<wgpu::wgc::id::BindGroupLayoutId>,
-    pub(crate) config: wgpu::wgt::SurfaceConfiguration<Vec<wgpu::wgt::TextureFormat>>,
+    pub(crate) camera_bind_group_layout: AutoDropId<wgpu::wgc::id::BindGroupLayoutId>,
+    pub(crate) config: SurfaceConfig,
+    pub(crate) options: RenderOptions,
+    pipelines: PipelineRegistry,
+    pub(crate) mipmaps: MipmapGenerator,
+    force_fallback_adapter: bool,
+    lost: Arc<AtomicBool>,
+}
+impl RenderContext {
+    /// Returns the pipeline for `key`, building it on first use.
+    pub(crate) fn pipeline(&self, key: &PipelineKey) -> Result<Arc<AutoDropId<wgpu::wgc::id::RenderPipelineId>>, RenderError> {
+        self.pipelines.get(&self.pipeline_builder(), key)
+    }
+
+    pub(crate) fn pipeline_cache_id(&self) -> Option<wgpu::wgc::id::PipelineCacheId> {
+        self.pipelines.cache_id()
+    }
+
+    fn pipeline_builder(&self) -> PipelineBuilder<'_> {
+        PipelineBuilder { instance: &self.instance, device_id: self.device.id, layout_id: self.pipeline_layout.id, options: &self.options }
+    }
+
+    /// The pipeline drawing with the built-in shader into the format of this context.
+    pub fn default_pipeline_key(&self) -> PipelineKey {
+        PipelineKey::new(color_format(&self.config))
+    }
+
+    /// Registers WGSL `source` under `name`, for pipeline keys to refer to.
+    /// Replacing a shader rebuilds its pipelines. On failure, the last good ones are kept in use.
+    pub fn register_shader(&self, name: impl Into<Cow<'static, str>>, source: impl Into<Cow<'static, str>>) -> Result<(), RenderError> {
+        self.pipelines.register(&self.pipeline_builder(), name.into(), source.into())
+    }
+
+    /// Rebuilds the pipelines of the built-in shader from WGSL `source`.
+    /// On failure, the last good pipelines are kept in use.
+    pub fn reload_shader(&self, source: &str) -> Result<(), RenderError> {
+        self.register_shader(pipeline::DEFAULT_SHADER, source.to_string())
+    }
+
+    /// Number of distinct pipelines built so far.
+    pub fn cached_pipelines(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    /// The file the pipeline cache is saved to, if the adapter supports one.
+    pub fn pipeline_cache_path(&self) -> Option<&Path> {
+        self.pipelines.cache().map(PipelineCache::path)
+    }
+
+    /// Writes the compiled pipelines to `pipeline_cache_path`, to be reused by the next run.
+    pub fn save_pipeline_cache(&self) -> std::io::Result<()> {
+        match self.pipelines.cache() {
+            Some(cache) => cache.save(&self.instance),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn begin_encode(&self, label: &'static str) -> Result<AutoDropId<wgpu::wgc::id::CommandEncoderId>, RenderError> {
+        let desc = wgpu::wgt::CommandEncoderDescriptor { label: Some(Cow::Borrowed(label)) };
+        let (encoder_id, err) = self.instance.0.device_create_command_encoder(self.device.id, &desc, None);
+        let encoder = self.instance.as_auto_drop(encoder_id);
+        if let Some(err) = err { return Err(RenderError::resource(label, err)) }
+
+        Ok(encoder)
+    }
+
+    /// Finishes `encoder` and submits it alone to the queue.
+    pub(crate) fn submit(&self, encoder: AutoDropId<wgpu::wgc::id::CommandEncoderId>, label: &'static str) -> Result<(), RenderError> {
+        let desc = wgpu::wgt::CommandBufferDescriptor { label: Some(Cow::Borrowed(label)) };
+        let (buffer_id, err) = self.instance.0.command_encoder_finish(encoder.id, &desc, None);
+        let buffer = self.instance.as_auto_drop(buffer_id);
+        if let Some((label, err)) = err { return Err(RenderError::encode(label, err)) }
+
+        if let Err((_, err)) = self.instance.0.queue_submit(self.queue.id, &[buffer.id]) {
+            return Err(RenderError::submit(err))
+        }
+        Ok(())
+    }
+
+    pub(crate) fn surface_capabilities(&self, surface_id: wgpu::wgc::id::SurfaceId) -> Result<wgpu::wgt::SurfaceCapabilities, RenderError> {
+        self.instance.0.surface_get_capabilities(surface_id, self.adapter.id).map_err(RenderError::surface)
+    }
+
+    /// Negotiates the configuration of a surface, which may differ from the others (e.g. on another monitor).
+    pub(crate) fn surface_config(&self, surface_id: wgpu::wgc::id::SurfaceId, present_modes: &[wgpu::wgt::PresentMode]) -> Result<SurfaceConfig, RenderError> {
+        surface_config(&self.instance, self.adapter.id, surface_id, &self.options, present_modes)
+    }
+
+    pub fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    /// The features enabled on the device, those of the optional ones the adapter has.
+    pub fn features(&self) -> wgpu::wgt::Features {
+        self.instance.0.device_features(self.device.id)
+    }
+
+    /// Blocks until the work submitted so far is done, e.g. to keep frames from queueing up while measuring them.
+    pub fn wait_idle(&self) -> Result<(), RenderError> {
+        self.instance.0.device_poll(self.device.id, wgpu::wgt::PollType::wait_indefinitely()).map_err(RenderError::device)?;
+        Ok(())
+    }
+
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Acquire)
+    }
+
+    /// Registers `callback` to be invoked with the driver message when the device is lost.
+    /// Explicitly destroying the device is not reported.
+    pub fn on_device_lost(&self, callback: impl FnOnce(String) + Send + 'static) {
+        let lost = self.lost.clone();
+
+        self.instance.0.device_set_device_lost_closure(self.device.id, Box::new(move |reason, message| {
+            if let wgpu::wgt::DeviceLostReason::Unknown = reason {
+                lost.store(true, Ordering::Release);
+                callback(message);
+            }
+        }));
+    }
 }
 
-pub fn init_render_context(target: Box<dyn AsRawWindow + 'static>) -> Result<RenderContext, anyhow::Error> {
+/// Creates a rendering context on an adapter able to present to `target`.
+/// The surface made for the check is released, renderers create their own with `create_surface`.
+pub fn init_render_context(target: Box<dyn AsRawWindow + 'static>, options: RenderOptions) -> Result<RenderContext, RenderError> {
     let desc = wgpu::wgt::InstanceDescriptor {
         backends: wgpu::wgt::Backends::PRIMARY,
         ..Default::default()
     };
     let instance = WgpuInstance(Arc::new(wgpu::wgc::global::Global::new("gpu", &desc, None)));
 
-    let handle = target.get_handle().unwrap();
-    let surface_id = unsafe { instance.0.instance_create_surface(handle.display_handle, handle.window_handle, None) }.unwrap();
+    let handle = target.get_handle().map_err(RenderError::surface)?;
+    let surface_id = unsafe { instance.0.instance_create_surface(handle.display_handle, handle.window_handle, None) }.map_err(RenderError::surface)?;
+    let surface = instance.as_auto_drop(surface_id);
+
+    let device = request_device(&instance, Some(surface.id), false, &options)?;
+    let (adapter, device_id, _) = &device;
+
+    let config = surface_config(&instance, adapter.id, surface.id, &options, &options.present_modes)?;
+
+    let options = supported_options(&instance, adapter.id, color_format(&config), options);
+    let pipelines = PipelineRegistry::new(default_shaders(), load_pipeline_cache(&instance, adapter.id, *device_id, &options)?);
+    create_render_context(instance, device, config, options, pipelines, false)
+}
+
+/// Creates a rendering context without any window.
+/// Renderers made from it have to draw into an offscreen target (see `WgpuRenderer::new_offscreen`).
+/// `force_fallback_adapter` picks a software adapter so that rendering works on GPU-less machines.
+pub fn init_headless_context(force_fallback_adapter: bool, options: RenderOptions) -> Result<RenderContext, RenderError> {
+    let desc = wgpu::wgt::InstanceDescriptor {
+        // GL is included because it is often the only backend available on display-less machines (e.g. llvmpipe)
+        backends: wgpu::wgt::Backends::all(),
+        ..Default::default()
+    };
+    let instance = WgpuInstance(Arc::new(wgpu::wgc::global::Global::new("gpu", &desc, None)));
+
+    let device = request_device(&instance, None, force_fallback_adapter, &options)?;
+    let (adapter, device_id, _) = &device;
+    let format = if options.hdr { wgpu::wgt::TextureFormat::Rgba16Float } else { HEADLESS_FORMAT };
+    let options = supported_options(&instance, adapter.id, format, options);
+    let pipelines = PipelineRegistry::new(default_shaders(), load_pipeline_cache(&instance, adapter.id, *device_id, &options)?);
+
+    let config = wgpu::wgt::SurfaceConfiguration {
+        usage: wgpu::wgt::TextureUsages::RENDER_ATTACHMENT | wgpu::wgt::TextureUsages::COPY_SRC,
+        format,
+        width: 0,
+        height: 0,
+        present_mode: wgpu::wgt::PresentMode::default(),
+        desired_maximum_frame_latency: options.desired_maximum_frame_latency,
+        alpha_mode: wgpu::wgt::CompositeAlphaMode::default(),
+        view_formats: vec![],
+    };
+
+    create_render_context(instance, device, config, options, pipelines, force_fallback_adapter)
+}
+
+/// Creates a fresh device, queue and pipelines to replace a lost context.
+/// The instance is shared with `lost`, so surfaces created from it stay usable with the new context.
+pub fn recreate_render_context(lost: &RenderContext, compatible_surface: Option<wgpu::wgc::id::SurfaceId>) -> Result<RenderContext, RenderError> {
+    let instance = lost.instance.clone();
+    let device = request_device(&instance, compatible_surface, lost.force_fallback_adapter, &lost.options)?;
+    let (adapter, device_id, _) = &device;
+    // The new adapter may differ from the lost one
+    let options = supported_options(&instance, adapter.id, color_format(&lost.config), lost.options.clone());
+    let pipelines = PipelineRegistry::new(lost.pipelines.shaders(), load_pipeline_cache(&instance, adapter.id, *device_id, &options)?);
+
+    create_render_context(instance, device, lost.config.clone(), options, pipelines, lost.force_fallback_adapter)
+}
+
+pub(crate) type SurfaceConfig = wgpu::wgt::SurfaceConfiguration<Vec<wgpu::wgt::TextureFormat>>;
+
+fn surface_config(instance: &WgpuInstance, adapter_id: wgpu::wgc::id::AdapterId, surface_id: wgpu::wgc::id::SurfaceId, options: &RenderOptions, present_modes: &[wgpu::wgt::PresentMode]) -> Result<SurfaceConfig, RenderError> {
+    let caps = instance.0.surface_get_capabilities(surface_id, adapter_id).map_err(RenderError::surface)?;
+    let srgb_views = instance.0.adapter_downlevel_capabilities(adapter_id).flags.contains(wgpu::wgt::DownlevelFlags::SURFACE_VIEW_FORMATS);
+    let format = select_surface_format(&caps.formats, options.hdr, srgb_views)
+        .ok_or_else(|| RenderError::surface("The surface supports no format"))?
+    ;
+
+    Ok(wgpu::wgt::SurfaceConfiguration {
+        // COPY_SRC lets frames be captured straight from the surface, but not every surface offers it
+        usage: wgpu::wgt::TextureUsages::RENDER_ATTACHMENT | (caps.usages & wgpu::wgt::TextureUsages::COPY_SRC),
+        format: format.format,
+        width: 0,
+        height: 0,
+        present_mode: select_present_mode(present_modes, &caps.present_modes),
+        desired_maximum_frame_latency: options.desired_maximum_frame_latency,
+        alpha_mode: match caps.alpha_modes.contains(&options.alpha_mode) {
+            true => options.alpha_mode,
+            false => wgpu::wgt::CompositeAlphaMode::Auto,
+        },
+        view_formats: match format.view_format == format.format {
+            true => vec![],
+            false => vec![format.view_format],
+        },
+    })
+}
+
+/// The format frames are drawn in: the view format of the surface, if any.
+pub(crate) fn color_format(config: &SurfaceConfig) -> wgpu::wgt::TextureFormat {
+    config.view_formats.first().copied().unwrap_or(config.format)
+}
+
+/// The format of the textures of a surface, and the one of the views frames are drawn into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SurfaceFormat {
+    pub format: wgpu::wgt::TextureFormat,
+    /// The sRGB variant of `format` when the surface only offers it without, the same format otherwise.
+    pub view_format: wgpu::wgt::TextureFormat,
+}
+
+/// Picks `Rgba16Float` for `hdr`, then the first sRGB format, then a format with an sRGB view where `srgb_views` are supported,
+/// then the first format. Returns `None` when nothing is supported.
+pub fn select_surface_format(supported: &[wgpu::wgt::TextureFormat], hdr: bool, srgb_views: bool) -> Option<SurfaceFormat> {
+    use wgpu::wgt::TextureFormat;
+
+    let same = |format: TextureFormat| SurfaceFormat { format, view_format: format };
+
+    let hdr_format = supported.iter().copied()
+        .find(|format| hdr && *format == TextureFormat::Rgba16Float)
+        .map(same)
+    ;
+    let srgb_format = || supported.iter().copied()
+        .find(|format| format.is_srgb())
+        .map(same)
+    ;
+    let srgb_view_format = || supported.iter().copied()
+        .find(|format| srgb_views && format.add_srgb_suffix() != *format)
+        .map(|format| SurfaceFormat { format, view_format: format.add_srgb_suffix() })
+    ;
+
+    hdr_format
+        .or_else(srgb_format)
+        .or_else(srgb_view_format)
+        .or_else(|| supported.first().copied().map(same))
+}
+
+/// Picks the first of `preferences` in `supported`, resolving the automatic modes, and falls back to `Fifo`.
+pub fn select_present_mode(preferences: &[wgpu::wgt::PresentMode], supported: &[wgpu::wgt::PresentMode]) -> wgpu::wgt::PresentMode {
+    use wgpu::wgt::PresentMode;
+
+    preferences.iter()
+        .flat_map(|preference| match preference {
+            PresentMode::AutoVsync => vec![PresentMode::FifoRelaxed, PresentMode::Fifo],
+            PresentMode::AutoNoVsync => vec![PresentMode::Immediate, PresentMode::Mailbox, PresentMode::Fifo],
+            mode => vec![*mode],
+        })
+        .find(|mode| supported.contains(mode))
+        .unwrap_or(PresentMode::Fifo)
+}
+
+pub const HEADLESS_FORMAT: wgpu::wgt::TextureFormat = wgpu::wgt::TextureFormat::Rgba8UnormSrgb;
+
+fn default_shaders() -> HashMap<Cow<'static, str>, Cow<'static, str>> {
+    HashMap::from([(Cow::Borrowed(pipeline::DEFAULT_SHADER), Cow::Borrowed(include_str!("shader.wgsl")))])
+}
+
+fn load_pipeline_cache(instance: &WgpuInstance, adapter_id: wgpu::wgc::id::AdapterId, device_id: wgpu::wgc::id::DeviceId, options: &RenderOptions) -> Result<Option<PipelineCache>, RenderError> {
+    if ! instance.0.device_features(device_id).contains(wgpu::wgt::Features::PIPELINE_CACHE) { return Ok(None) }
+
+    options.pipeline_cache_dir.as_deref()
+        .and_then(|dir| PipelineCache::locate(instance, adapter_id, dir))
+        .map(|path| PipelineCache::load(instance, device_id, path))
+        .transpose()
+}
+
+/// Lowers the sample count to the largest one supported by both the color and the depth formats.
+/// Without `TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES`, which is not requested, the device only accepts 1 and 4 samples.
+fn supported_options(instance: &WgpuInstance, adapter_id: wgpu::wgc::id::AdapterId, color_format: wgpu::wgt::TextureFormat, mut options: RenderOptions) -> RenderOptions {
+    let is_supported = |count: u32| {
+        let formats = std::iter::once(color_format).chain(options.depth_format);
+        formats.into_iter().all(|format| {
+            instance.0.adapter_get_texture_format_features(adapter_id, format).flags.sample_count_supported(count)
+        })
+    };
+
+    let requested = options.sample_count;
+    options.sample_count = [4, 1].into_iter()
+        .filter(|count| *count <= requested)
+        .find(|count| is_supported(*count))
+        .unwrap_or(1)
+    ;
+    if options.sample_count != requested {
+        log::warn!("Sample count {requested} is not supported, falling back to {}", options.sample_count);
+    }
 
+    options
+}
+
+fn request_device(instance: &WgpuInstance, compatible_surface: Option<wgpu::wgc::id::SurfaceId>, force_fallback_adapter: bool, options: &RenderOptions) -> Result<(AutoDropId<wgpu::wgc::id::AdapterId>, wgpu::wgc::id::DeviceId, wgpu::wgc::id::QueueId), RenderError> {
     let desc = wgpu::wgt::RequestAdapterOptions {
         power_preference: wgpu::wgt::PowerPreference::default(),
-        force_fallback_adapter: false,
-        compatible_surface: Some(surface_id),
+        force_fallback_adapter,
+        compatible_surface,
     };
-    let adapter_id: wgpu::wgc::id::AdapterId = instance.0.request_adapter(&desc, wgpu::wgt::Backends::all(), None)?;
+    let adapter_id: wgpu::wgc::id::AdapterId = instance.0.request_adapter(&desc, wgpu::wgt::Backends::all(), None).map_err(RenderError::device)?;
     let adapter = instance.as_auto_drop(adapter_id);
 
+    // Optional features are only requested when the adapter has them, pipelines fall back without them
+    let mut optional_features =
+        wgpu::wgt::Features::POLYGON_MODE_LINE | wgpu::wgt::Features::POLYGON_MODE_POINT |
+        wgpu::wgt::Features::ADDRESS_MODE_CLAMP_TO_BORDER | wgpu::wgt::Features::ADDRESS_MODE_CLAMP_TO_ZERO
+    ;
+    if options.pipeline_cache_dir.is_some() {
+        optional_features |= wgpu::wgt::Features::PIPELINE_CACHE;
+    }
+    let required_features = instance.0.adapter_features(adapter.id) & optional_features;
     let desc = wgpu::wgt::DeviceDescriptor {
         label: Some("Fetch the driver and the queue"),
-        required_features: wgpu::wgt::Features::empty(),
+        required_features,
         required_limits: wgpu::wgt::Limits::defaults(),
         experimental_features: wgpu::wgt::ExperimentalFeatures::disabled(),
         memory_hints: wgpu::wgt::MemoryHints::default(),
         trace: wgpu::wgt::Trace::Off,
     };
-    let (device_id, queue_id) = instance.0.adapter_request_device(adapter.id, &desc.map_label(|s| s.map(Cow::Borrowed)), None, None)?;
+    let (device_id, queue_id) = instance.0.adapter_request_device(adapter.id, &desc.map_label(|s| s.map(Cow::Borrowed)), None, None).map_err(RenderError::device)?;
 
-    let caps = instance.0.surface_get_capabilities(surface_id, adapter.id)?;
-    let format = caps.formats.iter().find(|fmt| fmt.is_srgb()).cloned().unwrap_or(caps.formats[0]);
-    let config = wgpu::wgt::SurfaceConfiguration {
-        usage: wgpu::wgt::TextureUsages::RENDER_ATTACHMENT,
-        format,
-        width: 0,
-        height: 0,
-        present_mode: caps.present_modes[0],
-        desired_maximum_frame_latency: 2,
-        alpha_mode: caps.alpha_modes[0],
-        view_formats: vec![],
-    };
+    Ok((adapter, device_id, queue_id))
+}
 
+fn create_render_context(
+    instance: WgpuInstance,
+    (adapter, device_id, queue_id): (AutoDropId<wgpu::wgc::id::AdapterId>, wgpu::wgc::id::DeviceId, wgpu::wgc::id::QueueId),
+    config: SurfaceConfig,
+    options: RenderOptions,
+    pipelines: PipelineRegistry,
+    force_fallback_adapter: bool,
+) -> Result<RenderContext, RenderError> {
     let desc = wgpu::wgc::binding_model::BindGroupLayoutDescriptor {
-        label: Some("Diffuse texture bind group layout").map(Cow::Borrowed),
+        label: Some(Cow::Borrowed("Diffuse texture bind group layout")),
         entries: Cow::Borrowed(&[
             wgpu::wgt::BindGroupLayoutEntry {
                 binding: 0,
@@ -94,89 +437,62 @@ pub fn init_render_context(target: Box<dyn AsRawWindow + 'static>) -> Result<Ren
     };
     let (layout_id, err) = instance.0.device_create_bind_group_layout(device_id, &desc, None);
     let bing_group_layout = instance.as_auto_drop(layout_id);
-    if let Some(err) = err { anyhow::bail!("{err}") }
+    if let Some(err) = err { return Err(RenderError::resource("Diffuse texture bind group layout", err)) }
+
+    let desc = wgpu::wgc::binding_model::BindGroupLayoutDescriptor {
+        label: Some(Cow::Borrowed("Camera bind group layout")),
+        entries: Cow::Borrowed(&[
+            wgpu::wgt::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::wgt::ShaderStages::VERTEX,
+                ty: wgpu::wgt::BindingType::Buffer {
+                    ty: wgpu::wgt::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ]),
+    };
+    let (layout_id, err) = instance.0.device_create_bind_group_layout(device_id, &desc, None);
+    let camera_bind_group_layout = instance.as_auto_drop(layout_id);
+    if let Some(err) = err { return Err(RenderError::resource("Camera bind group layout", err)) }
 
     let desc = wgpu::wgc::binding_model::PipelineLayoutDescriptor {
-        label: Some("Render pipeline layout").map(Cow::Borrowed),
-        bind_group_layouts: Cow::Borrowed(&[bing_group_layout.id]),
+        label: Some(Cow::Borrowed("Render pipeline layout")),
+        bind_group_layouts: Cow::Borrowed(&[bing_group_layout.id, camera_bind_group_layout.id]),
         immediate_size: 0,
     };
     let (layout_id, err) = instance.0.device_create_pipeline_layout(device_id, &desc, None);
     let layout = instance.as_auto_drop(layout_id);
-    if let Some(err) = err { anyhow::bail!("{err}") }
-
-    let source = wgpu::wgc::pipeline::ShaderModuleSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl")));
-    let desc = wgpu::wgc::pipeline::ShaderModuleDescriptor {
-        label: Some("Shader").map(Cow::Borrowed),
-        runtime_checks: wgpu::wgt::ShaderRuntimeChecks::checked(),
-    };
-    let (shader_id, err) = instance.0.device_create_shader_module(device_id, &desc, source, None);
-    let shader = instance.as_auto_drop(shader_id);
-    if let Some(err) = err { anyhow::bail!("{err}") }
-
-    let desc = wgpu::wgc::pipeline::RenderPipelineDescriptor {
-        label: Some("Render pipeline").map(Cow::Borrowed),
-        layout: Some(layout.id),
-        vertex: wgpu::wgc::pipeline::VertexState {
-            stage: wgpu::wgc::pipeline::ProgrammableStageDescriptor {
-                module: shader.id,
-                entry_point: Some("vs_main").map(Cow::Borrowed),
-                constants: wgpu::naga::back::PipelineConstants::default(),
-                zero_initialize_workgroup_memory: false,
-            },
-            buffers: Cow::Borrowed(&[crate::Vertex::desc()]),
-        },
-        fragment: Some(wgpu::wgc::pipeline::FragmentState {
-            stage: wgpu::wgc::pipeline::ProgrammableStageDescriptor {
-                module: shader.id,
-                entry_point: Some("fs_main").map(Cow::Borrowed),
-                constants: wgpu::naga::back::PipelineConstants::default(),
-                zero_initialize_workgroup_memory: false,
-            },
-            targets: Cow::Borrowed(&[
-                Some(wgpu::wgt::ColorTargetState {
-                    format,
-                    blend: Some(wgpu::wgt::BlendState::REPLACE),
-                    write_mask: wgpu::wgt::ColorWrites::ALL,
-                })
-            ]),
-        }),
-        primitive: wgpu::wgt::PrimitiveState {
-            topology: wgpu::wgt::PrimitiveTopology::TriangleList,
-            strip_index_format: None,
-            front_face: wgpu::wgt::FrontFace::Ccw,
-            cull_mode: Some(wgpu::wgt::Face::Back),
-            polygon_mode: wgpu::wgt::PolygonMode::Fill,
-            unclipped_depth: false,
-            conservative: false,
-        },
-        multisample: wgpu::wgt::MultisampleState {
-            count: 1,
-            mask: !0,
-            alpha_to_coverage_enabled: false,
-        },
-        depth_stencil: None,
-        multiview_mask: None,
-        cache: None,
-    };
-    let (pipeline_id, err) = instance.0.device_create_render_pipeline(device_id, &desc, None);
-    let pipeline = instance.as_auto_drop(pipeline_id);
-    if let Some(err) = err { anyhow::bail!("{err}") }
+    if let Some(err) = err { return Err(RenderError::resource("Render pipeline layout", err)) }
 
-    Ok(RenderContext{
+    let context = RenderContext{
+        adapter,
         device: instance.as_auto_drop(device_id),
         queue: instance.as_auto_drop(queue_id),
-        pipeline,
+        pipeline_layout: layout,
         bing_group_layout,
+        camera_bind_group_layout,
         instance,
         config,
-    })
+        options,
+        pipelines,
+        mipmaps: MipmapGenerator::default(),
+        force_fallback_adapter,
+        lost: Arc::new(AtomicBool::new(false)),
+    };
+    context.on_device_lost(|_| {});
+    // Built upfront, so that an invalid shader fails the creation of the context
+    context.pipeline(&context.default_pipeline_key())?;
+
+    Ok(context)
 }
 
-pub fn create_surface(context: &RenderContext, target: impl AsRawWindow) -> Result<wgpu::wgc::id::SurfaceId, anyhow::Error> {
-    let handle = target.get_handle()?;
+pub fn create_surface(context: &RenderContext, target: impl AsRawWindow) -> Result<wgpu::wgc::id::SurfaceId, RenderError> {
+    let handle = target.get_handle().map_err(RenderError::surface)?;
     let surface_id = unsafe {
-        context.instance.0.instance_create_surface(handle.display_handle, handle.window_handle, None)?
+        context.instance.0.instance_create_surface(handle.display_handle, handle.window_handle, None).map_err(RenderError::surface)?
     };
     Ok(surface_id)
 }
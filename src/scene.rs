@@ -0,0 +1,244 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use glam::{Mat3, Mat4, Vec3};
+
+use crate::{Vertex, mesh::{Indices, MeshData}};
+
+#[derive(Debug)]
+pub enum SceneError {
+    /// The glTF document, its buffers or its images could not be loaded.
+    Import(gltf::Error),
+    /// A primitive has no vertex positions.
+    MissingPositions { mesh: usize },
+    /// An index of a primitive refers to a vertex beyond its `vertex_count`.
+    IndexOutOfRange { mesh: usize, index: u32, vertex_count: usize },
+    /// The pixel format of a base color image cannot be converted to RGBA.
+    UnsupportedImage(gltf::image::Format),
+    /// The scene does not contain any triangle.
+    Empty,
+}
+
+impl std::fmt::Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SceneError::Import(err) => write!(f, "Failed to import glTF (cause: {err})"),
+            SceneError::MissingPositions { mesh } => write!(f, "Mesh #{mesh} has no vertex positions"),
+            SceneError::IndexOutOfRange { mesh, index, vertex_count } => write!(f, "Mesh #{mesh} refers to the vertex {index} out of {vertex_count}"),
+            SceneError::UnsupportedImage(format) => write!(f, "Unsupported base color image format: {format:?}"),
+            SceneError::Empty => write!(f, "glTF scene has no triangles"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Import(err) => Some(err),
+            SceneError::MissingPositions { .. } | SceneError::IndexOutOfRange { .. } | SceneError::UnsupportedImage(_) | SceneError::Empty => None,
+        }
+    }
+}
+
+/// Meshes of a glTF scene, ready to be uploaded with `WgpuRenderer::set_scene`.
+#[derive(Clone, Debug, Default)]
+pub struct Scene {
+    pub meshes: Vec<SceneMesh>,
+}
+
+/// A primitive placed in the scene.
+/// The node transforms are baked into the vertices, and the base color factor into the image.
+#[derive(Clone, Debug)]
+pub struct SceneMesh {
+    pub name: Option<String>,
+    pub data: MeshData,
+    pub base_color: Arc<image::RgbaImage>,
+    /// The material blends by the alpha of the base color, instead of being opaque.
+    pub alpha_blend: bool,
+    /// The back faces of the material are visible.
+    pub double_sided: bool,
+}
+
+/// Imports a `.gltf` or `.glb` file. Buffers and images referred by relative URIs are resolved from its directory.
+pub fn load_gltf(path: impl AsRef<Path>) -> Result<Scene, SceneError> {
+    let (document, buffers, images) = gltf::import(path).map_err(SceneError::Import)?;
+    build_scene(&document, &buffers, &images)
+}
+
+/// Imports a glTF document or a GLB container. External URIs cannot be resolved.
+pub fn load_gltf_from_bytes(bytes: &[u8]) -> Result<Scene, SceneError> {
+    let (document, buffers, images) = gltf::import_slice(bytes).map_err(SceneError::Import)?;
+    build_scene(&document, &buffers, &images)
+}
+
+fn build_scene(document: &gltf::Document, buffers: &[gltf::buffer::Data], images: &[gltf::image::Data]) -> Result<Scene, SceneError> {
+    let mut scene = Scene::default();
+    let mut base_colors = HashMap::new();
+
+    // Without a default scene, the first one is shown as most viewers do
+    if let Some(root) = document.default_scene().or_else(|| document.scenes().next()) {
+        for node in root.nodes() {
+            add_node(&mut scene, &mut base_colors, &node, &Mat4::IDENTITY, buffers, images)?;
+        }
+    }
+    if scene.meshes.is_empty() { return Err(SceneError::Empty) }
+
+    Ok(scene)
+}
+
+fn add_node(scene: &mut Scene, base_colors: &mut HashMap<Option<usize>, Arc<image::RgbaImage>>, node: &gltf::Node, parent: &Mat4, buffers: &[gltf::buffer::Data], images: &[gltf::image::Data]) -> Result<(), SceneError> {
+    // Column major as glTF stores them
+    let transform = *parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+
+    if let Some(mesh) = node.mesh() {
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                log::warn!("Skipped a primitive of mesh #{} (mode: {:?})", mesh.index(), primitive.mode());
+                continue;
+            }
+
+            let mut data = read_primitive(&primitive, mesh.index(), buffers)?;
+            apply_transform(&mut data, &transform);
+
+            // Primitives sharing a material share the image as well
+            let material = primitive.material();
+            let base_color = match base_colors.get(&material.index()) {
+                Some(image) => image.clone(),
+                None => {
+                    let image = Arc::new(read_base_color(&material, images)?);
+                    base_colors.insert(material.index(), image.clone());
+                    image
+                }
+            };
+
+            scene.meshes.push(SceneMesh {
+                name: mesh.name().map(str::to_string),
+                data,
+                base_color,
+                // Masked materials are drawn opaque, as the shader has no alpha cutoff
+                alpha_blend: material.alpha_mode() == gltf::material::AlphaMode::Blend,
+                double_sided: material.double_sided(),
+            });
+        }
+    }
+
+    for child in node.children() {
+        add_node(scene, base_colors, &child, &transform, buffers, images)?;
+    }
+    Ok(())
+}
+
+fn read_primitive(primitive: &gltf::Primitive, mesh: usize, buffers: &[gltf::buffer::Data]) -> Result<MeshData, SceneError> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+
+    let positions = reader.read_positions().ok_or(SceneError::MissingPositions { mesh })?.collect::<Vec<_>>();
+    let normals = reader.read_normals().map(|normals| normals.collect::<Vec<_>>());
+    let texture_coords = reader.read_tex_coords(0).map(|coords| coords.into_f32().collect::<Vec<_>>());
+
+    let vertices = positions.iter().enumerate()
+        .map(|(i, &position)| Vertex {
+            position,
+            // glTF puts the origin of the texture at the top left as wgpu does
+            texture_coords: texture_coords.as_ref().and_then(|coords| coords.get(i).copied()).unwrap_or([0.0, 0.0]),
+            normal: normals.as_ref().and_then(|normals| normals.get(i).copied()).unwrap_or([0.0, 0.0, 0.0]),
+        })
+        .collect::<Vec<_>>()
+    ;
+
+    let indices = match reader.read_indices() {
+        Some(gltf::mesh::util::ReadIndices::U8(indices)) => Indices::U16(indices.map(u16::from).collect()),
+        Some(gltf::mesh::util::ReadIndices::U16(indices)) => Indices::U16(indices.collect()),
+        Some(gltf::mesh::util::ReadIndices::U32(indices)) => Indices::U32(indices.collect()),
+        // Non-indexed primitives draw the vertices in order
+        None => Indices::U32((0..vertices.len() as u32).collect()),
+    };
+
+    // glTF validation does not check the index values
+    if let Some(index) = indices.max().filter(|&index| index as usize >= vertices.len()) {
+        return Err(SceneError::IndexOutOfRange { mesh, index, vertex_count: vertices.len() })
+    }
+
+    let mut data = MeshData { vertices, indices };
+    if normals.is_none() {
+        data.compute_normals();
+    }
+    Ok(data)
+}
+
+/// Moves the vertices into the scene space.
+fn apply_transform(data: &mut MeshData, transform: &Mat4) {
+    // The inverse transpose keeps normals perpendicular under non-uniform scaling
+    let normal_matrix = Mat3::from_mat4(*transform).inverse().transpose();
+    let mirrored = transform.determinant() < 0.0;
+
+    for vertex in &mut data.vertices {
+        vertex.position = transform.transform_point3(Vec3::from(vertex.position)).into();
+        // A degenerate transform has no inverse, and keeps the normals as they are
+        vertex.normal = (normal_matrix * Vec3::from(vertex.normal)).try_normalize().map_or(vertex.normal, Into::into);
+    }
+
+    // A mirroring transform turns the faces inside out, so the winding is restored
+    if mirrored {
+        data.indices = match &data.indices {
+            Indices::U16(indices) => Indices::U16(indices.chunks_exact(3).flat_map(|t| [t[0], t[2], t[1]]).collect()),
+            Indices::U32(indices) => Indices::U32(indices.chunks_exact(3).flat_map(|t| [t[0], t[2], t[1]]).collect()),
+        };
+    }
+}
+
+/// Converts the base color texture to RGBA and multiplies it by the base color factor.
+/// Without a texture, a single texel of the factor is made.
+fn read_base_color(material: &gltf::Material, images: &[gltf::image::Data]) -> Result<image::RgbaImage, SceneError> {
+    let pbr = material.pbr_metallic_roughness();
+    let factor = pbr.base_color_factor();
+
+    let Some(info) = pbr.base_color_texture() else {
+        let texel = std::array::from_fn(|i| match i {
+            3 => to_unorm8(factor[3]),
+            _ => to_unorm8(linear_to_srgb(factor[i])),
+        });
+        return Ok(image::RgbaImage::from_pixel(1, 1, image::Rgba(texel)));
+    };
+    if info.tex_coord() != 0 {
+        log::warn!("Base color texture uses the texture coordinates #{}, but only #0 is supported", info.tex_coord());
+    }
+
+    let data = &images[info.texture().source().index()];
+    let pixels = match data.format {
+        gltf::image::Format::R8G8B8A8 => data.pixels.clone(),
+        gltf::image::Format::R8G8B8 => data.pixels.chunks_exact(3).flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255]).collect(),
+        format => return Err(SceneError::UnsupportedImage(format)),
+    };
+    let mut image = image::RgbaImage::from_raw(data.width, data.height, pixels).ok_or(SceneError::UnsupportedImage(data.format))?;
+
+    if factor != [1.0; 4] {
+        for texel in image.pixels_mut() {
+            // The texture is sRGB encoded while the factor is linear
+            for (i, channel) in texel.0.iter_mut().enumerate() {
+                *channel = match i {
+                    3 => to_unorm8(*channel as f32 / 255.0 * factor[3]),
+                    _ => to_unorm8(linear_to_srgb(srgb_to_linear(*channel as f32 / 255.0) * factor[i])),
+                };
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    match value <= 0.04045 {
+        true => value / 12.92,
+        false => ((value + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    match value <= 0.0031308 {
+        true => value * 12.92,
+        false => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+    }
+}
+
+fn to_unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
@@ -3,6 +3,7 @@
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) texture_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
 }
 
 struct VertexOutput {
@@ -10,13 +11,20 @@ struct VertexOutput {
     @location(0) texture_coords: vec2<f32>,
 };
 
+struct CameraUniform {
+    view_projection: mat4x4<f32>,
+};
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
 @vertex
 fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
     var out: VertexOutput;
     out.texture_coords = model.texture_coords;
-    out.clip_position = vec4<f32>(model.position, 1.0);
+    out.clip_position = camera.view_projection * vec4<f32>(model.position, 1.0);
     return out;
 }
 
@@ -0,0 +1,58 @@
+use std::{path::PathBuf, time::{Duration, SystemTime}};
+
+use winit::event_loop::EventLoopProxy;
+
+use crate::runtime;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Polls the modification time of a WGSL file and sends its source to the event loop whenever it changes.
+/// The first poll always sends, so the file on disk overrides the embedded shader from the start.
+pub(crate) fn spawn(path: PathBuf, proxy: EventLoopProxy<runtime::UserEvent>) -> std::io::Result<()> {
+    std::thread::Builder::new()
+        .name("shader-watch".into())
+        .spawn(move || {
+            let mut last_modified: Option<SystemTime> = None;
+            // Failures are logged once until the file can be read again, instead of on every poll
+            let mut failing = false;
+
+            loop {
+                match std::fs::metadata(&path).and_then(|meta| meta.modified()) {
+                    Ok(modified) if last_modified != Some(modified) => {
+                        match std::fs::read_to_string(&path) {
+                            Ok(source) => {
+                                // Only a successful read consumes the change, a failed one is retried on the next poll
+                                last_modified = Some(modified);
+                                if failing {
+                                    log::info!("Watching {} again", path.display());
+                                    failing = false;
+                                }
+                                let event = runtime::UserEvent::ReloadShader { path: path.clone(), source };
+                                // The event loop has gone
+                                if proxy.send_event(event).is_err() { break }
+                            }
+                            Err(err) if ! failing => {
+                                log::warn!("Failed to read {} (cause: {err})", path.display());
+                                failing = true;
+                            }
+                            Err(_) => {}
+                        }
+                    }
+                    Ok(_) if failing => {
+                        log::info!("Watching {} again", path.display());
+                        failing = false;
+                    }
+                    Ok(_) => {}
+                    Err(err) if ! failing => {
+                        log::warn!("Failed to watch {}, waiting for it to come back (cause: {err})", path.display());
+                        failing = true;
+                    }
+                    Err(_) => {}
+                }
+
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        })?;
+
+    Ok(())
+}
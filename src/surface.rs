@@ -0,0 +1,81 @@
+use wgpu::wgt::SurfaceStatus;
+
+use crate::error::RenderError;
+
+/// Minimal view of a presentable surface, so that the recovery logic can be driven without a real window.
+pub trait SurfaceLayer {
+    type Frame;
+
+    /// Acquires the next frame together with the status reported by the surface.
+    fn acquire(&mut self) -> Result<(Option<Self::Frame>, SurfaceStatus), RenderError>;
+    /// Applies the current configuration again.
+    fn configure(&mut self) -> Result<(), RenderError>;
+    /// Shows the acquired frame.
+    fn present(&mut self) -> Result<(), RenderError>;
+    /// Gives the acquired frame back without showing it.
+    fn discard(&mut self);
+}
+
+#[derive(Debug)]
+pub enum Acquired<F> {
+    /// The frame is ready. A suboptimal surface should be reconfigured after presenting it.
+    Frame { frame: F, suboptimal: bool },
+    /// Nothing can be drawn this time, try again with the next frame.
+    Skip(SurfaceStatus),
+}
+
+/// Acquires a frame, reconfiguring the surface once when it is outdated or lost.
+/// Only errors which cannot be solved by waiting for the next frame are returned.
+pub fn acquire_frame<L: SurfaceLayer>(layer: &mut L) -> Result<Acquired<L::Frame>, RenderError> {
+    let mut reconfigured = false;
+
+    loop {
+        match layer.acquire()? {
+            (Some(frame), SurfaceStatus::Good) => return Ok(Acquired::Frame { frame, suboptimal: false }),
+            (Some(frame), SurfaceStatus::Suboptimal) => return Ok(Acquired::Frame { frame, suboptimal: true }),
+            (_, SurfaceStatus::Timeout) => return Ok(Acquired::Skip(SurfaceStatus::Timeout)),
+            (_, status @ (SurfaceStatus::Outdated | SurfaceStatus::Lost)) if reconfigured => return Ok(Acquired::Skip(status)),
+            (_, SurfaceStatus::Outdated | SurfaceStatus::Lost) => {
+                layer.configure()?;
+                reconfigured = true;
+            }
+            (_, status) => return Err(RenderError::Acquire(status)),
+        }
+    }
+}
+
+/// Reconfigures a surface after presenting a suboptimal frame. A failure is only logged,
+/// since the next `acquire_frame` reconfigures again once the surface turns outdated or lost.
+pub fn reconfigure_suboptimal<L: SurfaceLayer>(layer: &mut L) {
+    if let Err(err) = layer.configure() {
+        log::warn!("Failed to reconfigure the suboptimal surface, retrying on the next frame (cause: {err})");
+    }
+}
+
+/// Holds an acquired frame until it is presented. Dropping it first discards the frame,
+/// so that a failure while drawing does not keep the surface from handing out the next one.
+pub struct PendingFrame<'a, L: SurfaceLayer> {
+    /// Taken once the frame is presented.
+    layer: Option<&'a mut L>,
+}
+impl<'a, L: SurfaceLayer> PendingFrame<'a, L> {
+    pub fn new(layer: &'a mut L) -> Self {
+        Self { layer: Some(layer) }
+    }
+
+    /// Presents the frame and hands the layer back, e.g. to reconfigure it.
+    pub fn present(mut self) -> Result<&'a mut L, RenderError> {
+        let layer = self.layer.take().expect("Frame is presented once");
+        // A failed present has consumed the frame as well
+        layer.present()?;
+        Ok(layer)
+    }
+}
+
+impl<L: SurfaceLayer> Drop for PendingFrame<'_, L> {
+    fn drop(&mut self) {
+        if let Some(layer) = self.layer.take() {
+            layer.discard();
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use std::{borrow::Cow, path::{Path, PathBuf}};
+
+use wgpu::wgc::id::{SamplerId, TextureId, TextureViewId};
+
+use crate::{error::RenderError, mipmap, runtime, wgpu_resource::AutoDropId};
+
+/// The image shown when no other texture is given.
+pub const HAPPY_TREE: &[u8] = include_bytes!("../assets/img/happy-tree.png");
+
+#[derive(Debug)]
+pub enum TextureError {
+    /// The image file could not be read.
+    Io { path: PathBuf, source: std::io::Error },
+    /// The image is neither PNG nor JPEG.
+    UnsupportedFormat(Option<image::ImageFormat>),
+    /// The image data is broken.
+    Decode(image::ImageError),
+    /// wgpu-core rejected to create a resource of the texture.
+    Gpu { label: &'static str, source: Box<dyn std::error::Error + Send + Sync + 'static> },
+}
+
+impl std::fmt::Display for TextureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextureError::Io { path, source } => write!(f, "Failed to read {} (cause: {source})", path.display()),
+            TextureError::UnsupportedFormat(Some(format)) => write!(f, "Unsupported image format: {format:?}"),
+            TextureError::UnsupportedFormat(None) => write!(f, "Unknown image format"),
+            TextureError::Decode(err) => write!(f, "Failed to decode image (cause: {err})"),
+            TextureError::Gpu { label, source } => write!(f, "Failed to create {label} (cause: {source})"),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TextureError::Io { source, .. } => Some(source),
+            TextureError::UnsupportedFormat(_) => None,
+            TextureError::Decode(err) => Some(err),
+            TextureError::Gpu { source, .. } => Some(source.as_ref()),
+        }
+    }
+}
+
+impl TextureError {
+    fn gpu(label: &'static str, err: impl std::error::Error + Send + Sync + 'static) -> Self {
+        TextureError::Gpu { label, source: Box::new(err) }
+    }
+}
+
+/// A sampled 2D texture uploaded to the GPU with a full mip chain, shareable between renderers.
+/// Images are stored as sRGB, while storage textures hold linear colors, so both are sampled as linear.
+pub struct Texture {
+    pub(crate) texture: AutoDropId<TextureId>,
+    pub(crate) view: AutoDropId<TextureViewId>,
+    pub(crate) sampler: AutoDropId<SamplerId>,
+    sampler_options: SamplerOptions,
+    size: (u32, u32),
+    format: wgpu::wgt::TextureFormat,
+    mip_level_count: u32,
+}
+impl Texture {
+    pub fn from_path(context: &runtime::RenderContext, path: impl AsRef<Path>) -> Result<Self, TextureError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|source| TextureError::Io { path: path.to_path_buf(), source })?;
+
+        Self::from_bytes(context, &bytes)
+    }
+
+    pub fn from_bytes(context: &runtime::RenderContext, bytes: &[u8]) -> Result<Self, TextureError> {
+        let format = match image::guess_format(bytes) {
+            Ok(format @ (image::ImageFormat::Png | image::ImageFormat::Jpeg)) => format,
+            Ok(format) => return Err(TextureError::UnsupportedFormat(Some(format))),
+            Err(_) => return Err(TextureError::UnsupportedFormat(None)),
+        };
+        let image = image::load_from_memory_with_format(bytes, format).map_err(TextureError::Decode)?.to_rgba8();
+
+        Self::from_image(context, &image)
+    }
+
+    pub fn from_image(context: &runtime::RenderContext, image: &image::RgbaImage) -> Result<Self, TextureError> {
+        let dims = image.dimensions();
+        let size = wgpu::wgt::Extent3d { width: dims.0, height: dims.1, depth_or_array_layers: 1 };
+        let texture = Self::new(context, dims, wgpu::wgt::TextureFormat::Rgba8UnormSrgb, wgpu::wgt::TextureUsages::COPY_DST)?;
+
+        let dest = wgpu::wgt::TexelCopyTextureInfo {
+            texture: texture.texture.id,
+            mip_level: 0,
+            origin: wgpu::wgt::Origin3d::ZERO,
+            aspect: wgpu::wgt::TextureAspect::All,
+        };
+        let layout = wgpu::wgt::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * dims.0),
+            rows_per_image: Some(dims.1),
+        };
+        context.instance.0.queue_write_texture(context.queue.id, &dest, image, &layout, &size)
+            .map_err(|err| TextureError::gpu("diffuse texture", err))?;
+        texture.generate_mipmaps(context).map_err(|err| TextureError::gpu("diffuse texture mipmaps", err))?;
+
+        Ok(texture)
+    }
+
+    /// Creates an uninitialized texture that compute shaders can write linear colors to, e.g. with an `ImageFilter`.
+    /// Only the level 0 is written, `generate_mipmaps` fills the others.
+    pub fn new_storage(context: &runtime::RenderContext, size: (u32, u32)) -> Result<Self, TextureError> {
+        // sRGB formats cannot be bound as storage
+        Self::new(context, size, wgpu::wgt::TextureFormat::Rgba8Unorm, wgpu::wgt::TextureUsages::STORAGE_BINDING)
+    }
+
+    fn new(context: &runtime::RenderContext, (width, height): (u32, u32), format: wgpu::wgt::TextureFormat, usage: wgpu::wgt::TextureUsages) -> Result<Self, TextureError> {
+        let mip_level_count = mipmap::full_mip_level_count((width, height));
+        let desc = wgpu::wgt::TextureDescriptor {
+            label: Some(Cow::Borrowed("Diffuse texture")),
+            size: wgpu::wgt::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::wgt::TextureDimension::D2,
+            format,
+            // Mipmaps are rendered from the previous level
+            usage: wgpu::wgt::TextureUsages::TEXTURE_BINDING | wgpu::wgt::TextureUsages::RENDER_ATTACHMENT | usage,
+            view_formats: vec![],
+        };
+        let (texture_id, err) = context.instance.0.device_create_texture(context.device.id, &desc, None);
+        let texture = context.instance.as_auto_drop(texture_id);
+        if let Some(err) = err { return Err(TextureError::gpu("diffuse texture", err)) }
+
+        let desc = wgpu::wgc::resource::TextureViewDescriptor::default();
+        let (view_id, err) = context.instance.0.texture_create_view(texture.id, &desc, None);
+        let view = context.instance.as_auto_drop(view_id);
+        if let Some(err) = err { return Err(TextureError::gpu("diffuse texture view", err)) }
+
+        let sampler_options = SamplerOptions::default();
+        let sampler = create_sampler(context, &sampler_options)?;
+
+        Ok(Self { texture, view, sampler, sampler_options, size: (width, height), format, mip_level_count })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn sampler_options(&self) -> &SamplerOptions {
+        &self.sampler_options
+    }
+
+    /// Replaces the sampler. Renderers pick it up when the texture is set to them again.
+    pub fn set_sampler(&mut self, context: &runtime::RenderContext, options: SamplerOptions) -> Result<(), TextureError> {
+        self.sampler = create_sampler(context, &options)?;
+        self.sampler_options = options;
+        Ok(())
+    }
+
+    pub fn mip_level_count(&self) -> u32 {
+        self.mip_level_count
+    }
+
+    /// Downsamples the level 0 into the other levels, once it has been written.
+    pub fn generate_mipmaps(&self, context: &runtime::RenderContext) -> Result<(), RenderError> {
+        context.mipmaps.generate(context, self)
+    }
+
+    pub(crate) fn format(&self) -> wgpu::wgt::TextureFormat {
+        self.format
+    }
+
+    pub(crate) fn create_level_view(&self, context: &runtime::RenderContext, level: u32) -> Result<AutoDropId<TextureViewId>, wgpu::wgc::resource::CreateTextureViewError> {
+        let desc = wgpu::wgc::resource::TextureViewDescriptor {
+            range: wgpu::wgt::ImageSubresourceRange { base_mip_level: level, mip_level_count: Some(1), ..Default::default() },
+            ..Default::default()
+        };
+        let (view_id, err) = context.instance.0.texture_create_view(self.texture.id, &desc, None);
+        let view = context.instance.as_auto_drop(view_id);
+        match err {
+            Some(err) => Err(err),
+            None => Ok(view),
+        }
+    }
+}
+
+/// How `fs_main` samples a texture. The default filters trilinearly and clamps to the edges.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SamplerOptions {
+    /// Addressing of the u, v and w coordinates out of `0.0..=1.0`.
+    pub address_modes: [wgpu::wgt::AddressMode; 3],
+    pub mag_filter: wgpu::wgt::FilterMode,
+    pub min_filter: wgpu::wgt::FilterMode,
+    pub mipmap_filter: wgpu::wgt::MipmapFilterMode,
+    pub lod_min_clamp: f32,
+    pub lod_max_clamp: f32,
+    /// Maximum anisotropy, from 1 (disabled) to 16. Above 1, every filter must be linear.
+    pub anisotropy_clamp: u16,
+    /// Makes a comparison sampler, which only binds to a `sampler_comparison` in the shader.
+    pub compare: Option<wgpu::wgt::CompareFunction>,
+    /// The color outside of the texture with `AddressMode::ClampToBorder`, where the adapter supports it.
+    pub border_color: Option<wgpu::wgt::SamplerBorderColor>,
+}
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self {
+            address_modes: [wgpu::wgt::AddressMode::ClampToEdge; 3],
+            mag_filter: wgpu::wgt::FilterMode::Linear,
+            min_filter: wgpu::wgt::FilterMode::Linear,
+            mipmap_filter: wgpu::wgt::MipmapFilterMode::Linear,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 32.0,
+            anisotropy_clamp: 1,
+            compare: None,
+            border_color: None,
+        }
+    }
+}
+impl SamplerOptions {
+    /// Shows the texels as sharp squares, e.g. to inspect pixel art.
+    pub fn nearest(self) -> Self {
+        Self {
+            mag_filter: wgpu::wgt::FilterMode::Nearest,
+            min_filter: wgpu::wgt::FilterMode::Nearest,
+            mipmap_filter: wgpu::wgt::MipmapFilterMode::Nearest,
+            anisotropy_clamp: 1,
+            ..self
+        }
+    }
+
+    /// Filters linearly with up to `clamp` samples along the direction of anisotropy.
+    pub fn anisotropic(self, clamp: u16) -> Self {
+        Self {
+            mag_filter: wgpu::wgt::FilterMode::Linear,
+            min_filter: wgpu::wgt::FilterMode::Linear,
+            mipmap_filter: wgpu::wgt::MipmapFilterMode::Linear,
+            anisotropy_clamp: clamp.clamp(1, 16),
+            ..self
+        }
+    }
+
+    /// Uses `mode` for every coordinate.
+    pub fn address_mode(self, mode: wgpu::wgt::AddressMode) -> Self {
+        Self { address_modes: [mode; 3], ..self }
+    }
+
+    pub fn is_nearest(&self) -> bool {
+        self.mag_filter == wgpu::wgt::FilterMode::Nearest
+    }
+}
+
+fn create_sampler(context: &runtime::RenderContext, options: &SamplerOptions) -> Result<AutoDropId<SamplerId>, TextureError> {
+    let desc = wgpu::wgc::resource::SamplerDescriptor {
+        label: Some(Cow::Borrowed("Diffuse texture sampler")),
+        address_modes: options.address_modes,
+        mag_filter: options.mag_filter,
+        min_filter: options.min_filter,
+        mipmap_filter: options.mipmap_filter,
+        lod_min_clamp: options.lod_min_clamp,
+        lod_max_clamp: options.lod_max_clamp,
+        compare: options.compare,
+        anisotropy_clamp: options.anisotropy_clamp,
+        border_color: options.border_color,
+    };
+    let (sampler_id, err) = context.instance.0.device_create_sampler(context.device.id, &desc, None);
+    let sampler = context.instance.as_auto_drop(sampler_id);
+    if let Some(err) = err { return Err(TextureError::gpu("diffuse texture sampler", err)) }
+
+    Ok(sampler)
+}
@@ -74,7 +74,7 @@ impl AutoDrop for wgpu::wgc::id::TextureViewId {
 
 impl AutoDrop for wgpu::wgc::id::SamplerId {
     fn drop_id(&self, instance: &wgpu::wgc::global::Global) {
-        let _ = instance.sampler_drop(*self);
+        instance.sampler_drop(*self);
     }
 }
 
@@ -113,3 +113,15 @@ impl AutoDrop for wgpu::wgc::id::BindGroupId {
         instance.bind_group_drop(*self);
     }
 }
+
+impl AutoDrop for wgpu::wgc::id::PipelineCacheId {
+    fn drop_id(&self, instance: &wgpu::wgc::global::Global) {
+        instance.pipeline_cache_drop(*self);
+    }
+}
+
+impl AutoDrop for wgpu::wgc::id::ComputePipelineId {
+    fn drop_id(&self, instance: &wgpu::wgc::global::Global) {
+        instance.compute_pipeline_drop(*self);
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use winit::{dpi::{PhysicalPosition, PhysicalSize}, event_loop::EventLoopProxy, window::{Fullscreen, WindowAttributes}};
+
+use crate::runtime::UserEvent;
+
+/// Names a window requested through `AppHandle`, before and after it is opened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WindowKey(u64);
+impl WindowKey {
+    pub(crate) fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// The event loop has exited, so the request cannot be delivered.
+#[derive(Debug)]
+pub struct EventLoopClosed;
+
+impl std::fmt::Display for EventLoopClosed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The event loop has exited")
+    }
+}
+
+impl std::error::Error for EventLoopClosed {}
+
+/// Sends window requests to the app from any thread. Requests on a window that has been closed are ignored.
+#[derive(Clone)]
+pub struct AppHandle {
+    proxy: EventLoopProxy<UserEvent>,
+}
+impl AppHandle {
+    pub(crate) fn new(proxy: EventLoopProxy<UserEvent>) -> Self {
+        Self { proxy }
+    }
+
+    /// Requests a new window. The returned key refers to it once it is opened.
+    pub fn open_window(&self, spec: WindowSpec) -> Result<WindowKey, EventLoopClosed> {
+        let key = WindowKey::next();
+        self.send(UserEvent::RequestNew { key, spec })?;
+        Ok(key)
+    }
+
+    pub fn close_window(&self, key: WindowKey) -> Result<(), EventLoopClosed> {
+        self.send(UserEvent::Close(key))
+    }
+
+    pub fn set_title(&self, key: WindowKey, title: impl Into<String>) -> Result<(), EventLoopClosed> {
+        self.send(UserEvent::SetTitle(key, title.into()))
+    }
+
+    /// Requests the inner size in physical pixels. The platform may clamp or ignore it.
+    pub fn resize(&self, key: WindowKey, size: (u32, u32)) -> Result<(), EventLoopClosed> {
+        self.send(UserEvent::Resize(key, size))
+    }
+
+    fn send(&self, event: UserEvent) -> Result<(), EventLoopClosed> {
+        self.proxy.send_event(event).map_err(|_| EventLoopClosed)
+    }
+}
+
+/// How a window is opened by `AppHandle::open_window`. Sizes and positions are in physical pixels.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WindowSpec {
+    pub title: String,
+    pub size: (u32, u32),
+    pub min_size: Option<(u32, u32)>,
+    pub max_size: Option<(u32, u32)>,
+    /// Position of the top left corner on the desktop. `None` lets the platform choose.
+    pub position: Option<(i32, i32)>,
+    pub resizable: bool,
+    pub decorations: bool,
+    /// Covers the current monitor without changing its video mode.
+    pub fullscreen: bool,
+}
+impl Default for WindowSpec {
+    fn default() -> Self {
+        Self {
+            title: APP_NAME.to_string(),
+            size: (1024, 768),
+            min_size: None,
+            max_size: None,
+            position: None,
+            resizable: true,
+            decorations: true,
+            fullscreen: false,
+        }
+    }
+}
+impl WindowSpec {
+    pub(crate) fn attributes(&self) -> WindowAttributes {
+        let size = |(width, height): (u32, u32)| PhysicalSize::new(width, height);
+
+        let mut attr = WindowAttributes::default()
+            .with_title(self.title.clone())
+            .with_inner_size(size(self.size))
+            .with_resizable(self.resizable)
+            .with_decorations(self.decorations)
+            .with_fullscreen(self.fullscreen.then_some(Fullscreen::Borderless(None)))
+        ;
+        if let Some(min_size) = self.min_size {
+            attr = attr.with_min_inner_size(size(min_size));
+        }
+        if let Some(max_size) = self.max_size {
+            attr = attr.with_max_inner_size(size(max_size));
+        }
+        if let Some((x, y)) = self.position {
+            attr = attr.with_position(PhysicalPosition::new(x, y));
+        }
+        platform_attributes(attr)
+    }
+}
+
+const APP_NAME: &str = "wgpu-core-demo";
+
+/// Names the windows after the app (`WM_CLASS` on X11, the app id on Wayland), so that the desktop groups them.
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+fn platform_attributes(attr: WindowAttributes) -> WindowAttributes {
+    use winit::platform::{wayland::WindowAttributesExtWayland, x11::WindowAttributesExtX11};
+
+    let attr = WindowAttributesExtX11::with_name(attr, APP_NAME, APP_NAME);
+    WindowAttributesExtWayland::with_name(attr, APP_NAME, APP_NAME)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd")))]
+fn platform_attributes(attr: WindowAttributes) -> WindowAttributes {
+    attr
+}
@@ -0,0 +1,83 @@
+//! Camera matrices and controls.
+
+use glam::{Vec3, Vec4};
+use wgpu_core_demo::camera::{Camera, Projection};
+
+fn assert_near(actual: Vec3, expected: Vec3) {
+    assert!(actual.abs_diff_eq(expected, 1e-5), "{actual} != {expected}");
+}
+
+fn clip(camera: &Camera, point: Vec3) -> Vec3 {
+    let clip = camera.view_projection() * Vec4::from((point, 1.0));
+    clip.truncate() / clip.w
+}
+
+#[test]
+fn default_camera_keeps_clip_space_on_square_viewports() {
+    let camera = Camera::new();
+
+    assert_near(clip(&camera, Vec3::new(-0.5, 0.25, 0.0)), Vec3::new(-0.5, 0.25, 0.5));
+}
+
+#[test]
+fn aspect_ratio_widens_the_view() {
+    let mut camera = Camera::new();
+    camera.set_aspect((200, 100));
+
+    assert_near(clip(&camera, Vec3::new(2.0, 1.0, 0.0)), Vec3::new(1.0, 1.0, 0.5));
+}
+
+#[test]
+fn zero_size_keeps_the_aspect_ratio() {
+    let mut camera = Camera::new();
+    camera.set_aspect((200, 100));
+    camera.set_aspect((0, 100));
+
+    assert_eq!(camera.aspect(), 2.0);
+}
+
+#[test]
+fn perspective_shrinks_distant_points() {
+    let mut camera = Camera::new();
+    camera.projection = Projection::Perspective { fov_y: std::f32::consts::FRAC_PI_2, near: 0.1, far: 10.0 };
+
+    let near = clip(&camera, Vec3::new(0.5, 0.0, 0.0));
+    let far = clip(&camera, Vec3::new(0.5, 0.0, -1.0));
+    assert!(far.x < near.x);
+}
+
+#[test]
+fn orbit_keeps_the_distance_to_the_target() {
+    let mut camera = Camera::new();
+    camera.orbit(1.0, 0.5);
+
+    assert!((camera.distance() - 1.0).abs() < 1e-5);
+    assert!(camera.eye.y > 0.0);
+}
+
+#[test]
+fn orbit_stops_before_the_pole() {
+    let mut camera = Camera::new();
+    camera.orbit(0.0, 10.0);
+
+    assert!(camera.eye.z > 0.0);
+    assert!(camera.view_projection().is_finite());
+}
+
+#[test]
+fn fly_moves_the_target_along() {
+    let mut camera = Camera::new();
+    camera.fly(0.5, 0.25, 0.0);
+
+    assert_near(camera.eye, Vec3::new(0.25, 0.0, 0.5));
+    assert_near(camera.target, Vec3::new(0.25, 0.0, -0.5));
+}
+
+#[test]
+fn toggled_projection_keeps_the_visible_extent() {
+    let projection = Projection::Orthographic { height: 2.0, near: 0.0, far: 2.0 };
+
+    let toggled = projection.toggled(1.0).toggled(1.0);
+    let Projection::Orthographic { height, .. } = toggled else { panic!("Expected orthographic, got {toggled:?}") };
+    assert!((height - 2.0).abs() < 1e-5);
+}
@@ -0,0 +1,31 @@
+//! Geometry shared by the integration tests.
+
+// Each test crate uses only some of the helpers
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use wgpu_core_demo::{Vertex, mesh::MeshData, scene::SceneMesh};
+
+/// A square covering the center of the viewport at depth `z`, facing the camera.
+/// The texture coordinates go from 0 to `uv_max` from left to right, and from 0 to 1 from top to bottom.
+pub fn square(z: f32, uv_max: f32) -> MeshData {
+    let vertex = |x: f32, y: f32| Vertex { position: [x, y, z], texture_coords: [(x + 0.5) * uv_max, 0.5 - y], normal: [0.0, 0.0, 1.0] };
+    MeshData::new(vec![vertex(-0.5, -0.5), vertex(0.5, -0.5), vertex(0.5, 0.5), vertex(-0.5, 0.5)], vec![0u16, 1, 2, 0, 2, 3])
+}
+
+/// The `square` at depth `z` as an opaque, single-sided scene mesh drawn with `base_color`.
+pub fn square_mesh(z: f32, base_color: image::RgbaImage) -> SceneMesh {
+    SceneMesh {
+        name: None,
+        data: square(z, 1.0),
+        base_color: Arc::new(base_color),
+        alpha_blend: false,
+        double_sided: false,
+    }
+}
+
+/// A single texel of `color`.
+pub fn solid(color: [u8; 4]) -> image::RgbaImage {
+    image::RgbaImage::from_pixel(1, 1, image::Rgba(color))
+}
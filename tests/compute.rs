@@ -0,0 +1,59 @@
+//! Compute shaders over storage buffers and textures.
+
+use std::sync::Arc;
+
+use wgpu_core_demo::{compute::{self, ComputeEncoder, ComputePipeline, ComputeResource, ImageFilter, StorageBuffer}, error::RenderError, render::WgpuRenderer, runtime::{self, RenderOptions}, texture::Texture};
+
+const DOUBLE: &str = r#"
+@group(0) @binding(0) var<storage, read_write> values: array<u32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x < arrayLength(&values)) {
+        values[id.x] = values[id.x] * 2u;
+    }
+}
+"#;
+
+#[test]
+fn dispatch_covers_every_invocation() {
+    let context = runtime::init_headless_context(true, RenderOptions::default()).unwrap();
+    let pipeline = ComputePipeline::new(&context, "Double", DOUBLE, "main").unwrap();
+    assert_eq!(pipeline.workgroup_size(), [64, 1, 1]);
+
+    let values = (0..100u32).collect::<Vec<_>>();
+    let buffer = StorageBuffer::new(&context, "Values", bytemuck::cast_slice(&values)).unwrap();
+    let bind_group = pipeline.bind_group(&context, 0, &[ComputeResource::Buffer(&buffer)]).unwrap();
+
+    let mut encoder = ComputeEncoder::new(&context).unwrap();
+    encoder.dispatch_for(&pipeline, &[&bind_group], [values.len() as u32, 1, 1]).unwrap();
+    encoder.submit().unwrap();
+
+    let bytes = buffer.read(&context).unwrap();
+    let doubled: &[u32] = bytemuck::cast_slice(&bytes);
+    assert_eq!(doubled, values.iter().map(|value| value * 2).collect::<Vec<_>>());
+}
+
+#[test]
+fn invalid_compute_shader_reports_its_location() {
+    let context = runtime::init_headless_context(true, RenderOptions::default()).unwrap();
+    let result = ComputePipeline::new(&context, "Broken", "@compute @workgroup_size(1)\nfn main() { let x = ; }", "main");
+    assert!(matches!(result, Err(RenderError::Shader { location: Some((2, _)), .. })));
+}
+
+#[test]
+fn filtered_texture_is_sampled_by_the_renderer() {
+    let context = Arc::new(runtime::init_headless_context(true, RenderOptions::default()).unwrap());
+    let image = image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255]));
+    let texture = Texture::from_image(&context, &image).unwrap();
+
+    let filter = ImageFilter::new(&context, "Grayscale", compute::GRAYSCALE).unwrap();
+    let filtered = filter.apply(&context, &texture).unwrap();
+    assert_eq!(filtered.size(), (4, 4));
+
+    let mut renderer = WgpuRenderer::new_offscreen(context.clone(), (64, 64), &filtered).unwrap();
+    renderer.render().unwrap();
+    let [r, g, b, _] = renderer.read_pixels().unwrap().get_pixel(32, 32).0;
+    assert!(r == g && g == b, "Expected gray, got {:?}", [r, g, b]);
+    assert!(r > 0);
+}
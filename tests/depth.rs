@@ -0,0 +1,64 @@
+//! Depth testing of overlapping meshes drawn offscreen.
+
+mod common;
+
+use std::sync::Arc;
+
+use wgpu_core_demo::{render::WgpuRenderer, runtime::{self, RenderOptions}, scene::{Scene, SceneMesh}, texture::{self, Texture}};
+
+/// A square covering the center of the viewport at depth `z`, filled with `color`.
+fn square(z: f32, color: [u8; 4]) -> SceneMesh {
+    common::square_mesh(z, common::solid(color))
+}
+
+/// Draws a red square in front, then a green one behind it.
+fn render_center(options: RenderOptions, size: (u32, u32)) -> [u8; 4] {
+    let context = Arc::new(runtime::init_headless_context(true, options).unwrap());
+    let texture = Texture::from_bytes(&context, texture::HAPPY_TREE).unwrap();
+    let mut renderer = WgpuRenderer::new_offscreen(context, (64, 64), &texture).unwrap();
+
+    let scene = Scene { meshes: vec![square(0.5, [255, 0, 0, 255]), square(-0.5, [0, 255, 0, 255])] };
+    renderer.set_scene(&scene).unwrap();
+    renderer.request_resize(size);
+    renderer.render().unwrap();
+
+    let image = renderer.read_pixels().unwrap();
+    image.get_pixel(size.0 / 2, size.1 / 2).0
+}
+
+#[test]
+fn nearer_mesh_wins_with_depth() {
+    assert_eq!(render_center(RenderOptions::default(), (64, 64)), [255, 0, 0, 255]);
+}
+
+#[test]
+fn pipeline_without_depth_draws_over_nearer_meshes() {
+    let context = Arc::new(runtime::init_headless_context(true, RenderOptions::default()).unwrap());
+    let texture = Texture::from_bytes(&context, texture::HAPPY_TREE).unwrap();
+    let mut renderer = WgpuRenderer::new_offscreen(context.clone(), (64, 64), &texture).unwrap();
+
+    let scene = Scene { meshes: vec![square(0.5, [255, 0, 0, 255]), square(-0.5, [0, 255, 0, 255])] };
+    renderer.set_scene(&scene).unwrap();
+    renderer.meshes_mut()[1].set_pipeline(Some(context.default_pipeline_key().without_depth()));
+    renderer.render().unwrap();
+
+    let image = renderer.read_pixels().unwrap();
+    assert_eq!(image.get_pixel(32, 32).0, [0, 255, 0, 255]);
+}
+
+#[test]
+fn depth_and_stencil_format_is_accepted() {
+    let options = RenderOptions { depth_format: Some(wgpu::wgt::TextureFormat::Depth24PlusStencil8), ..Default::default() };
+    assert_eq!(render_center(options, (64, 64)), [255, 0, 0, 255]);
+}
+
+#[test]
+fn last_mesh_wins_without_depth() {
+    let options = RenderOptions { depth_format: None, ..Default::default() };
+    assert_eq!(render_center(options, (64, 64)), [0, 255, 0, 255]);
+}
+
+#[test]
+fn depth_texture_follows_resize() {
+    assert_eq!(render_center(RenderOptions::default(), (96, 48)), [255, 0, 0, 255]);
+}
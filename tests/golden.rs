@@ -0,0 +1,117 @@
+//! Golden-image regression tests.
+//!
+//! Each test renders a frame offscreen on a software adapter and compares it against
+//! a reference PNG in `tests/golden`.
+//! Run with `UPDATE_GOLDEN=1` to (re)write the references from the current output.
+//! On mismatch, the actual frame and a diff image are written to `target/golden-diff`.
+
+use std::{path::PathBuf, sync::Arc};
+
+use wgpu_core_demo::{mesh::{Mesh, MeshData}, render::WgpuRenderer, runtime, texture::{self, Texture}};
+
+/// Allowed per-channel difference, absorbing rasterization differences between software adapters.
+const DEFAULT_TOLERANCE: u8 = 2;
+
+fn render_offscreen(size: (u32, u32)) -> Result<image::RgbaImage, anyhow::Error> {
+    let context = Arc::new(runtime::init_headless_context(true, runtime::RenderOptions::default())?);
+    let texture = Texture::from_bytes(&context, texture::HAPPY_TREE)?;
+    let mut renderer = WgpuRenderer::new_offscreen(context, size, &texture)?;
+    renderer.render()?;
+    Ok(renderer.read_pixels()?)
+}
+
+fn assert_golden(name: &str, actual: &image::RgbaImage, tolerance: u8) {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let reference_path = root.join("tests/golden").join(format!("{name}.png"));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        actual.save(&reference_path).unwrap();
+        return;
+    }
+
+    let expected = image::open(&reference_path)
+        .unwrap_or_else(|err| panic!("Failed to load reference image {} (cause: {err})", reference_path.display()))
+        .to_rgba8()
+    ;
+    assert_eq!(expected.dimensions(), actual.dimensions(), "Image size mismatch for `{name}`");
+
+    let mut diff = image::RgbaImage::new(actual.width(), actual.height());
+    let mut mismatches = 0;
+
+    for ((expected, actual), diff) in expected.pixels().zip(actual.pixels()).zip(diff.pixels_mut()) {
+        let delta = std::array::from_fn::<u8, 4, _>(|i| expected[i].abs_diff(actual[i]));
+        if delta.iter().any(|d| *d > tolerance) {
+            mismatches += 1;
+            *diff = image::Rgba([255, 0, 0, 255]);
+        }
+        else {
+            // Keep a faded copy of the expected image so that mismatches can be located
+            *diff = image::Rgba([expected[0] / 4, expected[1] / 4, expected[2] / 4, 255]);
+        }
+    }
+
+    if mismatches > 0 {
+        let out_dir = root.join("target/golden-diff");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        actual.save(out_dir.join(format!("{name}.actual.png"))).unwrap();
+        diff.save(out_dir.join(format!("{name}.diff.png"))).unwrap();
+
+        panic!("{mismatches} pixels of `{name}` differ by more than {tolerance} (see {})", out_dir.display());
+    }
+}
+
+#[test]
+fn textured_pentagon() {
+    let actual = render_offscreen((256, 256)).unwrap();
+    assert_golden("textured_pentagon", &actual, DEFAULT_TOLERANCE);
+}
+
+#[test]
+fn textured_pentagon_after_recreate() {
+    let context = Arc::new(runtime::init_headless_context(true, runtime::RenderOptions::default()).unwrap());
+    let texture = Texture::from_bytes(&context, texture::HAPPY_TREE).unwrap();
+    let mut renderer = WgpuRenderer::new_offscreen(context.clone(), (256, 256), &texture).unwrap();
+    renderer.render().unwrap();
+
+    // Simulates the recovery path taken after the device is lost
+    let context = Arc::new(runtime::recreate_render_context(&context, None).unwrap());
+    let texture = Texture::from_bytes(&context, texture::HAPPY_TREE).unwrap();
+    renderer.recreate(context, &texture).unwrap();
+    renderer.render().unwrap();
+
+    let actual = renderer.read_pixels().unwrap();
+    assert_golden("textured_pentagon", &actual, DEFAULT_TOLERANCE);
+}
+
+#[test]
+fn textured_pentagon_after_mesh_update() {
+    let context = Arc::new(runtime::init_headless_context(true, runtime::RenderOptions::default()).unwrap());
+    let texture = Texture::from_bytes(&context, texture::HAPPY_TREE).unwrap();
+    let mut renderer = WgpuRenderer::new_offscreen(context, (256, 256), &texture).unwrap();
+
+    // Draw a single triangle first, then restore the whole pentagon through the dirty update path
+    renderer.meshes_mut()[0].set_indices(vec![0u32, 1, 4]);
+    renderer.render().unwrap();
+    renderer.meshes_mut()[0].set_indices(vec![0u32, 1, 4, 1, 2, 4, 2, 3, 4]);
+    renderer.render().unwrap();
+
+    let actual = renderer.read_pixels().unwrap();
+    assert_golden("textured_pentagon", &actual, DEFAULT_TOLERANCE);
+}
+
+#[test]
+fn textured_pentagon_from_split_meshes() {
+    let context = Arc::new(runtime::init_headless_context(true, runtime::RenderOptions::default()).unwrap());
+    let texture = Texture::from_bytes(&context, texture::HAPPY_TREE).unwrap();
+    let mut renderer = WgpuRenderer::new_offscreen(context.clone(), (256, 256), &texture).unwrap();
+
+    // The same pentagon drawn as two meshes, mixing both index formats
+    let vertices = MeshData::pentagon().vertices;
+    let first = Mesh::new(&context, MeshData::new(vertices.clone(), vec![0u16, 1, 4])).unwrap();
+    let second = Mesh::new(&context, MeshData::new(vertices, vec![1u32, 2, 4, 2, 3, 4])).unwrap();
+    renderer.set_meshes(vec![first, second]);
+    renderer.render().unwrap();
+
+    let actual = renderer.read_pixels().unwrap();
+    assert_golden("textured_pentagon", &actual, DEFAULT_TOLERANCE);
+}
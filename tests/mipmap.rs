@@ -0,0 +1,45 @@
+//! Mip chains of loaded textures, filtered when minified.
+
+mod common;
+
+use std::sync::Arc;
+
+use wgpu_core_demo::{render::WgpuRenderer, runtime::{self, RenderOptions}, scene::Scene, texture::{self, Texture}};
+
+/// Alternating black and white texels.
+fn checkerboard(size: u32) -> image::RgbaImage {
+    image::RgbaImage::from_fn(size, size, |x, y| match (x + y) % 2 {
+        0 => image::Rgba([0, 0, 0, 255]),
+        _ => image::Rgba([255, 255, 255, 255]),
+    })
+}
+
+#[test]
+fn mip_chain_goes_down_to_one_texel() {
+    let context = runtime::init_headless_context(true, RenderOptions::default()).unwrap();
+    let texture = Texture::from_image(&context, &checkerboard(256)).unwrap();
+    assert_eq!(texture.mip_level_count(), 9);
+
+    let texture = Texture::from_image(&context, &image::RgbaImage::new(300, 20)).unwrap();
+    assert_eq!(texture.mip_level_count(), 9);
+
+    let texture = Texture::from_image(&context, &image::RgbaImage::new(1, 1)).unwrap();
+    assert_eq!(texture.mip_level_count(), 1);
+}
+
+#[test]
+fn minified_checkerboard_is_averaged() {
+    let context = Arc::new(runtime::init_headless_context(true, RenderOptions::default()).unwrap());
+    let texture = Texture::from_bytes(&context, texture::HAPPY_TREE).unwrap();
+    let mut renderer = WgpuRenderer::new_offscreen(context, (64, 64), &texture).unwrap();
+
+    // 200 texels across a square covering about 32 pixels, so that the samples fall between texels unevenly
+    renderer.set_scene(&Scene { meshes: vec![common::square_mesh(0.0, checkerboard(200))] }).unwrap();
+    renderer.render().unwrap();
+
+    let image = renderer.read_pixels().unwrap();
+    let row = (26..38).map(|x| image.get_pixel(x, 32).0[0]).collect::<Vec<_>>();
+    let (min, max) = (row.iter().min().unwrap(), row.iter().max().unwrap());
+    assert!(max - min < 16, "Expected a uniform gray, got {row:?}");
+    assert!(*min > 32 && *max < 224, "Expected a uniform gray, got {row:?}");
+}
@@ -0,0 +1,45 @@
+//! Multisampled rendering resolved into an offscreen target.
+
+use std::sync::Arc;
+
+use wgpu_core_demo::{render::WgpuRenderer, runtime::{self, RenderOptions}, texture::{self, Texture}};
+
+fn render_pentagon(sample_count: u32) -> (u32, image::RgbaImage) {
+    let options = RenderOptions { sample_count, ..Default::default() };
+    let context = Arc::new(runtime::init_headless_context(true, options).unwrap());
+    let sample_count = context.options().sample_count;
+
+    let texture = Texture::from_bytes(&context, texture::HAPPY_TREE).unwrap();
+    let mut renderer = WgpuRenderer::new_offscreen(context, (128, 128), &texture).unwrap();
+    renderer.render().unwrap();
+
+    (sample_count, renderer.read_pixels().unwrap())
+}
+
+#[test]
+fn unsupported_sample_count_falls_back() {
+    let (sample_count, _) = render_pentagon(3);
+    assert_eq!(sample_count, 1);
+
+    // 4 samples are guaranteed for the headless color and depth formats, and the largest count the device accepts
+    let (sample_count, _) = render_pentagon(16);
+    assert_eq!(sample_count, 4);
+}
+
+#[test]
+fn edges_are_blended_with_the_background() {
+    let (_, aliased) = render_pentagon(1);
+    let (sample_count, antialiased) = render_pentagon(4);
+    assert_eq!(sample_count, 4);
+
+    let background = aliased.get_pixel(0, 0);
+    let blended = aliased.pixels().zip(antialiased.pixels())
+        .filter(|(aliased, antialiased)| aliased != antialiased && *antialiased != background)
+        .count()
+    ;
+    assert!(blended > 0, "No pixel on the edges has been blended");
+
+    // The inside of the pentagon is left as it is
+    let center = |image: &image::RgbaImage| image.get_pixel(64, 64).0;
+    assert!(center(&aliased).iter().zip(center(&antialiased)).all(|(a, b)| a.abs_diff(b) <= 2));
+}
@@ -0,0 +1,75 @@
+//! OBJ models parsed into mesh data.
+
+use wgpu_core_demo::{mesh::Indices, obj::{self, ObjError}};
+
+const TWO_GROUPS: &str = "
+v 0.0 0.0 0.0
+v 1.0 0.0 0.0
+v 1.0 1.0 0.0
+v 0.0 1.0 0.0
+vt 0.0 0.0
+vt 1.0 0.0
+vt 1.0 1.0
+vt 0.0 1.0
+vn 0.0 0.0 1.0
+
+g quad
+f 1/1/1 2/2/1 3/3/1 4/4/1
+
+g triangle
+f 1 2 3
+";
+
+#[test]
+fn groups_become_separate_meshes() {
+    let groups = obj::load_from_bytes(TWO_GROUPS.as_bytes()).unwrap();
+
+    let names = groups.iter().map(|group| group.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["quad", "triangle"]);
+}
+
+#[test]
+fn polygons_are_triangulated() {
+    let groups = obj::load_from_bytes(TWO_GROUPS.as_bytes()).unwrap();
+
+    let quad = &groups[0].data;
+    assert_eq!(quad.vertices.len(), 4);
+    assert_eq!(quad.indices.len(), 6);
+    assert!(matches!(quad.indices, Indices::U16(_)));
+}
+
+#[test]
+fn texture_coords_are_flipped_vertically() {
+    let groups = obj::load_from_bytes(TWO_GROUPS.as_bytes()).unwrap();
+
+    let coords = groups[0].data.vertices.iter().map(|v| v.texture_coords).collect::<Vec<_>>();
+    assert_eq!(coords, [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]);
+}
+
+#[test]
+fn missing_normals_are_computed() {
+    let groups = obj::load_from_bytes(TWO_GROUPS.as_bytes()).unwrap();
+
+    let triangle = &groups[1].data;
+    assert!(triangle.vertices.iter().all(|v| v.normal == [0.0, 0.0, 1.0]));
+    assert!(triangle.vertices.iter().all(|v| v.texture_coords == [0.0, 0.0]));
+}
+
+#[test]
+fn model_without_faces_is_rejected() {
+    let err = obj::load_from_bytes(b"v 0.0 0.0 0.0\n").unwrap_err();
+    assert!(matches!(err, ObjError::Empty));
+}
+
+#[test]
+fn broken_face_is_rejected() {
+    let err = obj::load_from_bytes(b"v 0.0 0.0 0.0\nf 1 2 x\n").unwrap_err();
+    assert!(matches!(err, ObjError::Parse(_)));
+}
+
+#[test]
+fn out_of_range_face_is_rejected() {
+    let err = obj::load_from_bytes(b"v 0.0 0.0 0.0\nv 1.0 0.0 0.0\nv 0.0 1.0 0.0\nf 1 2 4\n").unwrap_err();
+    // tobj checks the faces already, the loader guards against it as well
+    assert!(matches!(err, ObjError::Parse(_) | ObjError::IndexOutOfRange { .. }));
+}
@@ -0,0 +1,165 @@
+//! Pipelines built per state and shared through the context.
+
+mod common;
+
+use std::sync::Arc;
+
+use wgpu_core_demo::{Vertex, mesh::{Mesh, MeshData}, pipeline::PipelineKey, render::WgpuRenderer, runtime::{self, RenderContext, RenderOptions}, scene::{Scene, SceneMesh}, texture::{self, Texture}};
+
+const SOLID_RED: &str = r#"
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.0, 0.0, 1.0);
+}
+"#;
+
+fn offscreen(context: &Arc<RenderContext>) -> WgpuRenderer {
+    let texture = Texture::from_bytes(context, texture::HAPPY_TREE).unwrap();
+    WgpuRenderer::new_offscreen(context.clone(), (64, 64), &texture).unwrap()
+}
+
+/// A square covering the center of the viewport, wound clockwise so that it faces away.
+fn back_facing_square() -> MeshData {
+    let vertex = |x, y| Vertex { position: [x, y, 0.0], texture_coords: [0.0, 0.0], normal: [0.0, 0.0, 1.0] };
+    MeshData::new(vec![vertex(-0.5, -0.5), vertex(-0.5, 0.5), vertex(0.5, 0.5), vertex(0.5, -0.5)], vec![0u16, 1, 2, 0, 2, 3])
+}
+
+/// A square covering the center of the viewport at depth `z`, filled with `color`.
+fn square(z: f32, color: [u8; 4], alpha_blend: bool) -> SceneMesh {
+    SceneMesh { alpha_blend, ..common::square_mesh(z, common::solid(color)) }
+}
+
+fn render_center(renderer: &mut WgpuRenderer) -> [u8; 4] {
+    renderer.render().unwrap();
+    renderer.read_pixels().unwrap().get_pixel(32, 32).0
+}
+
+#[test]
+fn renderers_share_the_pipeline_of_equal_keys() {
+    let context = Arc::new(runtime::init_headless_context(true, RenderOptions::default()).unwrap());
+    let mut first = offscreen(&context);
+    let mut second = offscreen(&context);
+
+    first.render().unwrap();
+    second.render().unwrap();
+    assert_eq!(context.cached_pipelines(), 1);
+}
+
+#[test]
+fn mesh_overrides_the_pipeline_of_the_renderer() {
+    let context = Arc::new(runtime::init_headless_context(true, RenderOptions::default()).unwrap());
+    context.register_shader("solid", SOLID_RED).unwrap();
+
+    let mut renderer = offscreen(&context);
+    let mut mesh = Mesh::new(&context, back_facing_square()).unwrap();
+    mesh.set_pipeline(Some(PipelineKey { shader: "solid".into(), ..context.default_pipeline_key() }));
+    renderer.set_meshes(vec![mesh]);
+    let background = render_center(&mut renderer);
+    assert_ne!(background, [255, 0, 0, 255], "Back faces must be culled");
+
+    renderer.meshes_mut()[0].set_pipeline(Some(PipelineKey { shader: "solid".into(), cull_mode: None, ..context.default_pipeline_key() }));
+    assert_eq!(render_center(&mut renderer), [255, 0, 0, 255]);
+    assert_eq!(context.cached_pipelines(), 3);
+
+    // The renderer key applies again once the override is cleared
+    renderer.meshes_mut()[0].set_pipeline(None);
+    renderer.set_pipeline_key(PipelineKey { cull_mode: None, ..context.default_pipeline_key() });
+    assert_ne!(render_center(&mut renderer), background);
+}
+
+#[test]
+fn invalid_shader_keeps_the_pipelines() {
+    let context = Arc::new(runtime::init_headless_context(true, RenderOptions::default()).unwrap());
+    let mut renderer = offscreen(&context);
+    let before = render_center(&mut renderer);
+
+    assert!(context.reload_shader("fn broken(").is_err());
+    assert!(context.register_shader("broken", "fn broken(").is_err());
+    assert_eq!(render_center(&mut renderer), before);
+    assert_eq!(context.cached_pipelines(), 1);
+}
+
+#[test]
+fn unregistered_shader_fails_to_draw() {
+    let context = Arc::new(runtime::init_headless_context(true, RenderOptions::default()).unwrap());
+    let mut renderer = offscreen(&context);
+
+    renderer.set_pipeline_key(PipelineKey { shader: "missing".into(), ..context.default_pipeline_key() });
+    assert!(renderer.render().is_err());
+}
+
+#[test]
+fn pipeline_cache_is_saved_to_the_directory() {
+    let dir = std::env::temp_dir().join(format!("wgpu-core-demo-pipeline-cache-{}", std::process::id()));
+    let options = RenderOptions { pipeline_cache_dir: Some(dir.clone()), ..Default::default() };
+    let context = Arc::new(runtime::init_headless_context(true, options).unwrap());
+    offscreen(&context).render().unwrap();
+
+    context.save_pipeline_cache().unwrap();
+    // Only some backends (Vulkan) have a pipeline cache
+    if let Some(path) = context.pipeline_cache_path() {
+        assert!(path.starts_with(&dir));
+        assert!(path.exists());
+
+        // The next run starts from the saved data
+        let options = RenderOptions { pipeline_cache_dir: Some(dir.clone()), ..Default::default() };
+        let context = Arc::new(runtime::init_headless_context(true, options).unwrap());
+        offscreen(&context).render().unwrap();
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn blended_mesh_shows_the_opaque_one_behind() {
+    let context = Arc::new(runtime::init_headless_context(true, RenderOptions::default()).unwrap());
+    let mut renderer = offscreen(&context);
+
+    // The sprite comes first, but is drawn after the opaque square behind it
+    let scene = Scene { meshes: vec![square(0.5, [255, 0, 0, 128], true), square(-0.5, [0, 255, 0, 255], false)] };
+    renderer.set_scene(&scene).unwrap();
+    let [r, g, b, _] = render_center(&mut renderer);
+    assert!(r > 100 && g > 100 && b < 10, "Expected red over green, got {:?}", [r, g, b]);
+}
+
+#[test]
+fn line_list_draws_the_pairs_of_indices() {
+    let context = Arc::new(runtime::init_headless_context(true, RenderOptions::default()).unwrap());
+    context.register_shader("solid", SOLID_RED).unwrap();
+
+    // A vertical line through the center of the viewport
+    let vertex = |y| Vertex { position: [0.0, y, 0.0], texture_coords: [0.0, 0.0], normal: [0.0, 0.0, 1.0] };
+    let mut mesh = Mesh::new(&context, MeshData::new(vec![vertex(-0.9), vertex(0.9)], vec![0u16, 1])).unwrap();
+    mesh.set_pipeline(Some(PipelineKey { shader: "solid".into(), ..context.default_pipeline_key() }.lines()));
+
+    let mut renderer = offscreen(&context);
+    renderer.set_meshes(vec![mesh]);
+    renderer.render().unwrap();
+
+    let image = renderer.read_pixels().unwrap();
+    let red = image::Rgba([255, 0, 0, 255]);
+    assert!((31..=32).any(|x| *image.get_pixel(x, 10) == red));
+    assert_ne!(*image.get_pixel(10, 10), red);
+}
+
+#[test]
+fn wireframe_is_drawn_or_falls_back_to_fill() {
+    let context = Arc::new(runtime::init_headless_context(true, RenderOptions::default()).unwrap());
+    let mut renderer = offscreen(&context);
+    let filled = render_center(&mut renderer);
+
+    renderer.set_wireframe(true);
+    let wireframe = render_center(&mut renderer);
+    assert_eq!(context.cached_pipelines(), 2);
+    match context.features().contains(wgpu::wgt::Features::POLYGON_MODE_LINE) {
+        true => assert_ne!(wireframe, filled, "The inside of the triangles must not be filled"),
+        false => assert_eq!(wireframe, filled, "Wireframe must fall back to fill"),
+    }
+
+    renderer.set_wireframe(false);
+    assert_eq!(render_center(&mut renderer), filled);
+}
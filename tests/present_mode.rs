@@ -0,0 +1,41 @@
+//! Present modes chosen from preferences, validated against the supported ones.
+
+use std::sync::Arc;
+
+use wgpu::wgt::PresentMode;
+use wgpu_core_demo::{render::WgpuRenderer, runtime::{self, RenderOptions}, texture::{self, Texture}};
+
+#[test]
+fn first_supported_preference_is_selected() {
+    let supported = [PresentMode::Fifo, PresentMode::Mailbox];
+    assert_eq!(runtime::select_present_mode(&[PresentMode::Immediate, PresentMode::Mailbox], &supported), PresentMode::Mailbox);
+    assert_eq!(runtime::select_present_mode(&[PresentMode::Immediate], &supported), PresentMode::Fifo);
+    assert_eq!(runtime::select_present_mode(&[], &supported), PresentMode::Fifo);
+}
+
+#[test]
+fn automatic_modes_resolve_to_supported_ones() {
+    let supported = [PresentMode::Fifo, PresentMode::FifoRelaxed, PresentMode::Mailbox, PresentMode::Immediate];
+    assert_eq!(runtime::select_present_mode(&[PresentMode::AutoVsync], &supported), PresentMode::FifoRelaxed);
+    assert_eq!(runtime::select_present_mode(&[PresentMode::AutoNoVsync], &supported), PresentMode::Immediate);
+
+    let supported = [PresentMode::Fifo, PresentMode::Mailbox];
+    assert_eq!(runtime::select_present_mode(&[PresentMode::AutoVsync], &supported), PresentMode::Fifo);
+    assert_eq!(runtime::select_present_mode(&[PresentMode::AutoNoVsync], &supported), PresentMode::Mailbox);
+}
+
+#[test]
+fn offscreen_renderer_keeps_drawing_after_changes() {
+    let options = RenderOptions { present_modes: vec![PresentMode::Immediate], desired_maximum_frame_latency: 1, ..Default::default() };
+    let context = Arc::new(runtime::init_headless_context(true, options).unwrap());
+    let texture = Texture::from_bytes(&context, texture::HAPPY_TREE).unwrap();
+    let mut renderer = WgpuRenderer::new_offscreen(context, (64, 64), &texture).unwrap();
+    assert_eq!(renderer.supported_present_modes().unwrap(), [PresentMode::Fifo]);
+    assert_eq!(renderer.present_mode(), PresentMode::Fifo);
+    assert_eq!(renderer.frame_latency(), 1);
+
+    assert_eq!(renderer.set_present_modes(&[PresentMode::AutoNoVsync]).unwrap(), PresentMode::Fifo);
+    renderer.set_frame_latency(3).unwrap();
+    assert_eq!(renderer.frame_latency(), 3);
+    renderer.render().unwrap();
+}
@@ -0,0 +1,105 @@
+//! Samplers chosen per texture.
+
+mod common;
+
+use std::sync::Arc;
+
+use wgpu_core_demo::{mesh::Mesh, render::WgpuRenderer, runtime::{self, RenderContext, RenderOptions}, scene::Scene, texture::{SamplerOptions, Texture}};
+
+/// A black texel on the left of a white one.
+fn black_and_white(context: &RenderContext) -> Texture {
+    let image = image::RgbaImage::from_fn(2, 1, |x, _| match x {
+        0 => image::Rgba([0, 0, 0, 255]),
+        _ => image::Rgba([255, 255, 255, 255]),
+    });
+    Texture::from_image(context, &image).unwrap()
+}
+
+/// Draws `texture` on a square covering the center of the viewport, with texture coordinates from 0 to `uv_max`,
+/// and returns the red channel along the middle row of the square.
+fn render_row(context: &Arc<RenderContext>, texture: &Texture, uv_max: f32) -> Vec<u8> {
+    let mut renderer = WgpuRenderer::new_offscreen(context.clone(), (64, 64), texture).unwrap();
+    renderer.set_meshes(vec![Mesh::new(context, common::square(0.0, uv_max)).unwrap()]);
+    renderer.render().unwrap();
+
+    let image = renderer.read_pixels().unwrap();
+    (20..44).map(|x| image.get_pixel(x, 32).0[0]).collect()
+}
+
+fn is_intermediate(value: u8) -> bool {
+    (32..224).contains(&value)
+}
+
+#[test]
+fn nearest_filter_keeps_the_texels_sharp() {
+    let context = Arc::new(runtime::init_headless_context(true, RenderOptions::default()).unwrap());
+    let mut texture = black_and_white(&context);
+
+    let linear = render_row(&context, &texture, 1.0);
+    assert!(linear.iter().any(|value| is_intermediate(*value)), "Expected a gradient, got {linear:?}");
+
+    texture.set_sampler(&context, SamplerOptions::default().nearest()).unwrap();
+    assert!(texture.sampler_options().is_nearest());
+    let nearest = render_row(&context, &texture, 1.0);
+    assert!(! nearest.iter().any(|value| is_intermediate(*value)), "Expected two colors, got {nearest:?}");
+}
+
+#[test]
+fn material_sampler_applies_to_the_scene() {
+    let context = Arc::new(runtime::init_headless_context(true, RenderOptions::default()).unwrap());
+    let texture = black_and_white(&context);
+    let mut renderer = WgpuRenderer::new_offscreen(context.clone(), (64, 64), &texture).unwrap();
+
+    let base_color = image::RgbaImage::from_fn(2, 1, |x, _| image::Rgba([255 * x as u8, 255 * x as u8, 255 * x as u8, 255]));
+    renderer.set_scene(&Scene { meshes: vec![common::square_mesh(0.0, base_color)] }).unwrap();
+    let render_row = |renderer: &mut WgpuRenderer| {
+        renderer.render().unwrap();
+        let image = renderer.read_pixels().unwrap();
+        (20..44).map(|x| image.get_pixel(x, 32).0[0]).collect::<Vec<_>>()
+    };
+
+    let linear = render_row(&mut renderer);
+    assert!(linear.iter().any(|value| is_intermediate(*value)), "Expected a gradient, got {linear:?}");
+
+    renderer.set_material_sampler(SamplerOptions::default().nearest()).unwrap();
+    let nearest = render_row(&mut renderer);
+    assert!(! nearest.iter().any(|value| is_intermediate(*value)), "Expected two colors, got {nearest:?}");
+}
+
+#[test]
+fn repeat_address_mode_tiles_the_texture() {
+    let context = Arc::new(runtime::init_headless_context(true, RenderOptions::default()).unwrap());
+    let mut texture = black_and_white(&context);
+
+    // The right half of the square is out of the texture, where the white edge is clamped
+    texture.set_sampler(&context, SamplerOptions::default().nearest()).unwrap();
+    let clamped = render_row(&context, &texture, 2.0);
+    let right = &clamped[clamped.len() / 2..];
+    assert!(right.iter().all(|value| *value > 128), "Expected white, got {right:?}");
+
+    texture.set_sampler(&context, SamplerOptions::default().nearest().address_mode(wgpu::wgt::AddressMode::Repeat)).unwrap();
+    let repeated = render_row(&context, &texture, 2.0);
+    let right = &repeated[repeated.len() / 2..];
+    assert!(right.iter().any(|value| *value < 128), "Expected the black texel again, got {right:?}");
+}
+
+#[test]
+fn anisotropic_filter_forces_linear_filters() {
+    let context = Arc::new(runtime::init_headless_context(true, RenderOptions::default()).unwrap());
+    let mut texture = black_and_white(&context);
+
+    let options = SamplerOptions::default().nearest().anisotropic(32);
+    assert_eq!(options.anisotropy_clamp, 16);
+    assert!(! options.is_nearest());
+    texture.set_sampler(&context, options).unwrap();
+    render_row(&context, &texture, 1.0);
+}
+
+#[test]
+fn comparison_sampler_does_not_bind_to_the_image() {
+    let context = Arc::new(runtime::init_headless_context(true, RenderOptions::default()).unwrap());
+    let mut texture = black_and_white(&context);
+    texture.set_sampler(&context, SamplerOptions { compare: Some(wgpu::wgt::CompareFunction::Less), ..Default::default() }).unwrap();
+
+    assert!(WgpuRenderer::new_offscreen(context.clone(), (64, 64), &texture).is_err());
+}
@@ -0,0 +1,155 @@
+//! glTF scenes imported from GLB containers built in memory.
+
+use std::sync::Arc;
+
+use wgpu_core_demo::{render::WgpuRenderer, runtime, scene::{self, SceneError}, texture::{self, Texture}};
+
+const TRIANGLE: [[f32; 3]; 3] = [[-0.5, -0.5, 0.0], [0.5, -0.5, 0.0], [0.0, 0.5, 0.0]];
+
+fn glb(nodes: &str, base_color_factor: [f32; 4]) -> Vec<u8> {
+    glb_with_material(nodes, &format!(r#"{{ "pbrMetallicRoughness": {{ "baseColorFactor": {base_color_factor:?} }} }}"#))
+}
+
+fn glb_with_material(nodes: &str, material: &str) -> Vec<u8> {
+    glb_with_indices(nodes, material, [0, 1, 2])
+}
+
+/// Packs a glTF document with a single triangle mesh into a GLB container.
+/// `nodes` and `material` are spliced into the JSON as they are.
+fn glb_with_indices(nodes: &str, material: &str, indices: [u16; 3]) -> Vec<u8> {
+    let mut bin = TRIANGLE.iter().flatten().flat_map(|v| v.to_le_bytes()).collect::<Vec<_>>();
+    bin.extend(indices.iter().flat_map(|i| i.to_le_bytes()));
+    bin.resize(bin.len().next_multiple_of(4), 0);
+
+    let json = format!(r#"{{
+        "asset": {{ "version": "2.0" }},
+        "scene": 0,
+        "scenes": [{{ "nodes": [0] }}],
+        "nodes": {nodes},
+        "meshes": [{{ "name": "triangle", "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "indices": 1, "material": 0 }}] }}],
+        "materials": [{material}],
+        "buffers": [{{ "byteLength": {} }}],
+        "bufferViews": [
+            {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+            {{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }}
+        ],
+        "accessors": [
+            {{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [-0.5, -0.5, 0.0], "max": [0.5, 0.5, 0.0] }},
+            {{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+        ]
+    }}"#, bin.len());
+    let mut json = json.into_bytes();
+    json.resize(json.len().next_multiple_of(4), b' ');
+
+    let mut glb = Vec::new();
+    glb.extend(b"glTF");
+    glb.extend(2u32.to_le_bytes());
+    glb.extend(((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+    glb.extend((json.len() as u32).to_le_bytes());
+    glb.extend(b"JSON");
+    glb.extend(json);
+    glb.extend((bin.len() as u32).to_le_bytes());
+    glb.extend(b"BIN\0");
+    glb.extend(bin);
+    glb
+}
+
+#[test]
+fn node_transforms_are_baked() {
+    let bytes = glb(r#"[{ "mesh": 0, "translation": [1.0, 2.0, 3.0] }]"#, [1.0; 4]);
+    let scene = scene::load_gltf_from_bytes(&bytes).unwrap();
+
+    assert_eq!(scene.meshes.len(), 1);
+    assert_eq!(scene.meshes[0].name.as_deref(), Some("triangle"));
+    assert_eq!(scene.meshes[0].data.vertices[0].position, [0.5, 1.5, 3.0]);
+}
+
+#[test]
+fn child_transforms_are_applied_after_the_parent() {
+    let bytes = glb(r#"[{ "children": [1], "translation": [1.0, 0.0, 0.0] }, { "mesh": 0, "scale": [2.0, 2.0, 2.0] }]"#, [1.0; 4]);
+    let scene = scene::load_gltf_from_bytes(&bytes).unwrap();
+
+    assert_eq!(scene.meshes[0].data.vertices[2].position, [1.0, 1.0, 0.0]);
+}
+
+#[test]
+fn mirrored_nodes_keep_front_faces_and_normals() {
+    let bytes = glb(r#"[{ "mesh": 0, "scale": [-1.0, 1.0, 1.0] }]"#, [1.0; 4]);
+    let scene = scene::load_gltf_from_bytes(&bytes).unwrap();
+
+    let data = &scene.meshes[0].data;
+    assert_eq!(data.indices.to_u32(), [0, 2, 1]);
+    assert!(data.vertices.iter().all(|v| v.normal == [0.0, 0.0, 1.0]));
+}
+
+#[test]
+fn base_color_factor_becomes_an_srgb_texel() {
+    let bytes = glb(r#"[{ "mesh": 0 }]"#, [1.0, 0.0, 0.0, 1.0]);
+    let scene = scene::load_gltf_from_bytes(&bytes).unwrap();
+
+    let image = &scene.meshes[0].base_color;
+    assert_eq!(image.dimensions(), (1, 1));
+    assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+}
+
+#[test]
+fn material_modes_are_imported() {
+    let scene = scene::load_gltf_from_bytes(&glb(r#"[{ "mesh": 0 }]"#, [1.0; 4])).unwrap();
+    assert!(! scene.meshes[0].alpha_blend);
+    assert!(! scene.meshes[0].double_sided);
+
+    let bytes = glb_with_material(r#"[{ "mesh": 0 }]"#, r#"{ "alphaMode": "BLEND", "doubleSided": true }"#);
+    let scene = scene::load_gltf_from_bytes(&bytes).unwrap();
+    assert!(scene.meshes[0].alpha_blend);
+    assert!(scene.meshes[0].double_sided);
+
+    let bytes = glb_with_material(r#"[{ "mesh": 0 }]"#, r#"{ "alphaMode": "MASK" }"#);
+    assert!(! scene::load_gltf_from_bytes(&bytes).unwrap().meshes[0].alpha_blend);
+}
+
+#[test]
+fn scene_without_meshes_is_rejected() {
+    let bytes = glb(r#"[{ }]"#, [1.0; 4]);
+
+    let err = scene::load_gltf_from_bytes(&bytes).unwrap_err();
+    assert!(matches!(err, SceneError::Empty));
+}
+
+#[test]
+fn out_of_range_index_is_rejected() {
+    let bytes = glb_with_indices(r#"[{ "mesh": 0 }]"#, "{}", [0, 1, 3]);
+
+    let err = scene::load_gltf_from_bytes(&bytes).unwrap_err();
+    assert!(matches!(err, SceneError::IndexOutOfRange { mesh: 0, index: 3, vertex_count: 3 }));
+}
+
+#[test]
+fn scene_is_drawn_with_its_base_color() {
+    let bytes = glb(r#"[{ "mesh": 0 }]"#, [0.0, 1.0, 0.0, 1.0]);
+    let scene = scene::load_gltf_from_bytes(&bytes).unwrap();
+
+    let context = Arc::new(runtime::init_headless_context(true, runtime::RenderOptions::default()).unwrap());
+    let texture = Texture::from_bytes(&context, texture::HAPPY_TREE).unwrap();
+    let mut renderer = WgpuRenderer::new_offscreen(context, (64, 64), &texture).unwrap();
+    renderer.set_scene(&scene).unwrap();
+    renderer.render().unwrap();
+
+    let image = renderer.read_pixels().unwrap();
+    assert_eq!(image.get_pixel(32, 32).0, [0, 255, 0, 255]);
+}
+
+#[test]
+fn meshes_of_a_material_share_its_texture() {
+    let bytes = glb(r#"[{ "children": [1, 2] }, { "mesh": 0 }, { "mesh": 0, "translation": [1.0, 0.0, 0.0] }]"#, [0.0, 1.0, 0.0, 1.0]);
+    let scene = scene::load_gltf_from_bytes(&bytes).unwrap();
+    assert_eq!(scene.meshes.len(), 2);
+
+    let context = Arc::new(runtime::init_headless_context(true, runtime::RenderOptions::default()).unwrap());
+    let texture = Texture::from_bytes(&context, texture::HAPPY_TREE).unwrap();
+    let mut renderer = WgpuRenderer::new_offscreen(context, (64, 64), &texture).unwrap();
+    renderer.set_scene(&scene).unwrap();
+    assert_eq!(renderer.material_count(), 1);
+
+    renderer.render().unwrap();
+    assert_eq!(renderer.read_pixels().unwrap().get_pixel(32, 32).0, [0, 255, 0, 255]);
+}
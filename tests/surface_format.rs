@@ -0,0 +1,61 @@
+//! Surface formats negotiated from the supported ones, and pipelines following the format of the target.
+
+use std::sync::Arc;
+
+use wgpu::wgt::TextureFormat;
+use wgpu_core_demo::{pipeline::PipelineKey, render::WgpuRenderer, runtime::{self, RenderOptions, SurfaceFormat}, texture::{self, Texture}};
+
+fn same(format: TextureFormat) -> Option<SurfaceFormat> {
+    Some(SurfaceFormat { format, view_format: format })
+}
+
+#[test]
+fn srgb_format_is_preferred() {
+    let supported = [TextureFormat::Bgra8Unorm, TextureFormat::Rgba16Float, TextureFormat::Bgra8UnormSrgb];
+    assert_eq!(runtime::select_surface_format(&supported, false, true), same(TextureFormat::Bgra8UnormSrgb));
+    assert_eq!(runtime::select_surface_format(&supported, true, true), same(TextureFormat::Rgba16Float));
+    assert_eq!(runtime::select_surface_format(&[TextureFormat::Bgra8UnormSrgb], true, true), same(TextureFormat::Bgra8UnormSrgb));
+}
+
+#[test]
+fn non_srgb_format_is_drawn_through_srgb_views() {
+    let supported = [TextureFormat::Rgb10a2Unorm, TextureFormat::Bgra8Unorm];
+    assert_eq!(runtime::select_surface_format(&supported, false, true), Some(SurfaceFormat { format: TextureFormat::Bgra8Unorm, view_format: TextureFormat::Bgra8UnormSrgb }));
+    assert_eq!(runtime::select_surface_format(&supported, false, false), same(TextureFormat::Rgb10a2Unorm));
+    assert_eq!(runtime::select_surface_format(&[], false, true), None);
+}
+
+#[test]
+fn pipelines_follow_the_format_of_the_renderer() {
+    let context = Arc::new(runtime::init_headless_context(true, RenderOptions::default()).unwrap());
+    let texture = Texture::from_bytes(&context, texture::HAPPY_TREE).unwrap();
+    let mut renderer = WgpuRenderer::new_offscreen(context.clone(), (64, 64), &texture).unwrap();
+    assert_eq!(renderer.color_format(), context.default_pipeline_key().format);
+    renderer.render().unwrap();
+    let expected = renderer.read_pixels().unwrap();
+
+    renderer.set_pipeline_key(PipelineKey { format: TextureFormat::Rgba16Float, ..context.default_pipeline_key() });
+    renderer.render().unwrap();
+    assert_eq!(renderer.read_pixels().unwrap(), expected);
+    assert_eq!(context.cached_pipelines(), 1);
+}
+
+#[test]
+fn hdr_frames_are_read_back_as_srgb() {
+    let render = |hdr| {
+        let context = Arc::new(runtime::init_headless_context(true, RenderOptions { hdr, ..RenderOptions::default() }).unwrap());
+        let texture = Texture::from_bytes(&context, texture::HAPPY_TREE).unwrap();
+        let mut renderer = WgpuRenderer::new_offscreen(context, (64, 64), &texture).unwrap();
+        renderer.render().unwrap();
+        (renderer.color_format(), renderer.read_pixels().unwrap())
+    };
+    let (format, expected) = render(false);
+    assert_eq!(format, runtime::HEADLESS_FORMAT);
+    let (format, actual) = render(true);
+    assert_eq!(format, TextureFormat::Rgba16Float);
+
+    // Half floats and the sRGB encoding of the hardware round differently
+    for (expected, actual) in expected.pixels().zip(actual.pixels()) {
+        assert!(expected.0.iter().zip(actual.0).all(|(e, a)| e.abs_diff(a) <= 2), "{expected:?} != {actual:?}");
+    }
+}
@@ -0,0 +1,150 @@
+//! Surface status transitions driven through a mock surface layer.
+
+use std::collections::VecDeque;
+
+use wgpu::wgt::SurfaceStatus;
+use wgpu_core_demo::{error::RenderError, surface::{self, Acquired, PendingFrame, SurfaceLayer}};
+
+/// Replays scripted acquire results and counts reconfigurations, presented and discarded frames.
+struct MockSurface {
+    script: VecDeque<(Option<u32>, SurfaceStatus)>,
+    configured: usize,
+    fail_configure: bool,
+    presented: usize,
+    discarded: usize,
+}
+impl MockSurface {
+    fn new(script: impl IntoIterator<Item = (Option<u32>, SurfaceStatus)>) -> Self {
+        Self { script: script.into_iter().collect(), configured: 0, fail_configure: false, presented: 0, discarded: 0 }
+    }
+}
+
+impl SurfaceLayer for MockSurface {
+    type Frame = u32;
+
+    fn acquire(&mut self) -> Result<(Option<u32>, SurfaceStatus), RenderError> {
+        Ok(self.script.pop_front().expect("Acquired more frames than scripted"))
+    }
+
+    fn configure(&mut self) -> Result<(), RenderError> {
+        self.configured += 1;
+        match self.fail_configure {
+            true => Err(RenderError::Surface("configure failed".into())),
+            false => Ok(()),
+        }
+    }
+
+    fn present(&mut self) -> Result<(), RenderError> {
+        self.presented += 1;
+        Ok(())
+    }
+
+    fn discard(&mut self) {
+        self.discarded += 1;
+    }
+}
+
+#[test]
+fn good_frame_is_drawn() {
+    let mut layer = MockSurface::new([(Some(1), SurfaceStatus::Good)]);
+
+    let acquired = surface::acquire_frame(&mut layer).unwrap();
+    assert!(matches!(acquired, Acquired::Frame { frame: 1, suboptimal: false }));
+    assert_eq!(layer.configured, 0);
+}
+
+#[test]
+fn suboptimal_frame_is_drawn_and_flagged() {
+    let mut layer = MockSurface::new([(Some(1), SurfaceStatus::Suboptimal)]);
+
+    let acquired = surface::acquire_frame(&mut layer).unwrap();
+    assert!(matches!(acquired, Acquired::Frame { frame: 1, suboptimal: true }));
+}
+
+#[test]
+fn outdated_surface_is_reconfigured() {
+    let mut layer = MockSurface::new([(None, SurfaceStatus::Outdated), (Some(2), SurfaceStatus::Good)]);
+
+    let acquired = surface::acquire_frame(&mut layer).unwrap();
+    assert!(matches!(acquired, Acquired::Frame { frame: 2, .. }));
+    assert_eq!(layer.configured, 1);
+}
+
+#[test]
+fn lost_surface_is_reconfigured() {
+    let mut layer = MockSurface::new([(None, SurfaceStatus::Lost), (Some(3), SurfaceStatus::Good)]);
+
+    let acquired = surface::acquire_frame(&mut layer).unwrap();
+    assert!(matches!(acquired, Acquired::Frame { frame: 3, .. }));
+    assert_eq!(layer.configured, 1);
+}
+
+#[test]
+fn still_outdated_after_reconfigure_skips_the_frame() {
+    let mut layer = MockSurface::new([(None, SurfaceStatus::Outdated), (None, SurfaceStatus::Outdated)]);
+
+    let acquired = surface::acquire_frame(&mut layer).unwrap();
+    assert!(matches!(acquired, Acquired::Skip(SurfaceStatus::Outdated)));
+    assert_eq!(layer.configured, 1);
+    assert!(layer.script.is_empty());
+}
+
+#[test]
+fn timeout_skips_the_frame() {
+    let mut layer = MockSurface::new([(None, SurfaceStatus::Timeout)]);
+
+    let acquired = surface::acquire_frame(&mut layer).unwrap();
+    assert!(matches!(acquired, Acquired::Skip(SurfaceStatus::Timeout)));
+    assert_eq!(layer.configured, 0);
+}
+
+#[test]
+fn unknown_status_is_fatal() {
+    let mut layer = MockSurface::new([(None, SurfaceStatus::Unknown)]);
+
+    let err = surface::acquire_frame(&mut layer).unwrap_err();
+    assert!(matches!(err, RenderError::Acquire(SurfaceStatus::Unknown)));
+    assert!(! err.is_recoverable());
+}
+
+#[test]
+fn failed_reconfigure_is_propagated() {
+    let mut layer = MockSurface::new([(None, SurfaceStatus::Lost)]);
+    layer.fail_configure = true;
+
+    let err = surface::acquire_frame(&mut layer).unwrap_err();
+    assert!(matches!(err, RenderError::Surface(_)));
+}
+
+#[test]
+fn failed_suboptimal_reconfigure_is_retried_on_the_next_acquire() {
+    let mut layer = MockSurface::new([(None, SurfaceStatus::Outdated), (Some(2), SurfaceStatus::Good)]);
+    layer.fail_configure = true;
+
+    surface::reconfigure_suboptimal(&mut layer);
+    assert_eq!(layer.configured, 1);
+
+    layer.fail_configure = false;
+    let acquired = surface::acquire_frame(&mut layer).unwrap();
+    assert!(matches!(acquired, Acquired::Frame { frame: 2, suboptimal: false }));
+    assert_eq!(layer.configured, 2);
+}
+
+#[test]
+fn frame_failing_to_draw_is_discarded() {
+    let mut layer = MockSurface::new([(Some(1), SurfaceStatus::Good), (Some(2), SurfaceStatus::Good)]);
+
+    let draw = |layer: &mut MockSurface, fail: bool| -> Result<(), RenderError> {
+        let Acquired::Frame { .. } = surface::acquire_frame(layer)? else { panic!("Expected a frame") };
+        let frame = PendingFrame::new(layer);
+        if fail { return Err(RenderError::Surface("draw failed".into())) }
+        frame.present()?;
+        Ok(())
+    };
+
+    assert!(draw(&mut layer, true).is_err());
+    assert_eq!((layer.presented, layer.discarded), (0, 1));
+
+    draw(&mut layer, false).unwrap();
+    assert_eq!((layer.presented, layer.discarded), (1, 1));
+}
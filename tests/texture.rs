@@ -0,0 +1,35 @@
+//! Textures rejected before anything is uploaded.
+
+use wgpu_core_demo::{runtime::{self, RenderOptions}, texture::{self, Texture, TextureError}};
+
+#[test]
+fn missing_file_is_reported_with_its_path() {
+    let context = runtime::init_headless_context(true, RenderOptions::default()).unwrap();
+    let path = std::env::temp_dir().join("wgpu-core-demo-missing-texture.png");
+
+    let Err(err) = Texture::from_path(&context, &path) else { panic!("Expected an error") };
+    assert!(matches!(&err, TextureError::Io { path: err_path, .. } if *err_path == path));
+}
+
+#[test]
+fn non_image_file_is_rejected() {
+    let context = runtime::init_headless_context(true, RenderOptions::default()).unwrap();
+    let path = std::env::temp_dir().join(format!("wgpu-core-demo-texture-{}.txt", std::process::id()));
+    std::fs::write(&path, "not an image").unwrap();
+
+    let result = Texture::from_path(&context, &path);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(TextureError::UnsupportedFormat(None))));
+
+    // A known format other than PNG and JPEG
+    let result = Texture::from_bytes(&context, b"GIF89a\x01\x00\x01\x00\x00\x00\x00;");
+    assert!(matches!(result, Err(TextureError::UnsupportedFormat(Some(image::ImageFormat::Gif)))));
+}
+
+#[test]
+fn truncated_png_fails_to_decode() {
+    let context = runtime::init_headless_context(true, RenderOptions::default()).unwrap();
+
+    let result = Texture::from_bytes(&context, &texture::HAPPY_TREE[..texture::HAPPY_TREE.len() / 2]);
+    assert!(matches!(result, Err(TextureError::Decode(_))));
+}